@@ -0,0 +1,117 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use encoding_rs::UTF_16BE;
+use regex::{Captures, Regex};
+use std::hint::black_box;
+use utf7_imap::{
+    decode_utf7_imap, decode_utf7_imap_lenient, encode, encode_utf7_imap, try_decode_utf7_imap,
+};
+
+/// Mailbox names as a server with a large mix of ASCII and international folders
+/// would list them.
+fn folder_list(len: usize) -> Vec<String> {
+    const PARENTS: &[&str] = &[
+        "INBOX",
+        "Archive",
+        "Отправленные",
+        "Gelöschte Elemente",
+        "受信トレイ",
+    ];
+    const CHILDREN: &[&str] = &[
+        "Projects",
+        "Rechnungen & Belege",
+        "Černá listina",
+        "客户",
+        "Drafts",
+    ];
+    (0..len)
+        .map(|i| {
+            let parent = PARENTS[i % PARENTS.len()];
+            let child = CHILDREN[i / PARENTS.len() % CHILDREN.len()];
+            encode_utf7_imap(format!("{}/{}/{}", parent, child, i))
+        })
+        .collect()
+}
+
+/// The regex-based decoder that `decode_utf7_imap` used before it was rewritten, kept as
+/// the baseline of the decoding benchmarks. It only handles well-formed names.
+fn regex_decode_utf7_imap(text: String) -> String {
+    let pattern = Regex::new(r"&([^-]*)-").unwrap();
+    pattern
+        .replace_all(&text, |cap: &Captures| {
+            let part = &cap[1];
+            if part.is_empty() {
+                return String::from("&");
+            }
+            let mut b64 = part.replace(',', "/");
+            while !b64.len().is_multiple_of(4) {
+                b64.push('=');
+            }
+            let utf16 = base64::decode(b64).unwrap();
+            UTF_16BE.decode(&utf16).0.into_owned()
+        })
+        .to_string()
+}
+
+fn decode(c: &mut Criterion) {
+    let names = folder_list(60_000);
+    let bytes = names.iter().map(String::len).sum::<usize>();
+    let mut group = c.benchmark_group("decode 60k folders");
+    group.throughput(Throughput::Bytes(bytes as u64));
+    group.bench_function("regex baseline", |b| {
+        b.iter(|| {
+            for name in &names {
+                black_box(regex_decode_utf7_imap(black_box(name.clone())));
+            }
+        })
+    });
+    group.bench_function("decode_utf7_imap", |b| {
+        b.iter(|| {
+            for name in &names {
+                black_box(decode_utf7_imap(black_box(name.clone())));
+            }
+        })
+    });
+    group.bench_function("try_decode_utf7_imap", |b| {
+        b.iter(|| {
+            for name in &names {
+                let _ = black_box(try_decode_utf7_imap(black_box(name)));
+            }
+        })
+    });
+    group.bench_function("decode_utf7_imap_lenient", |b| {
+        b.iter(|| {
+            for name in &names {
+                black_box(decode_utf7_imap_lenient(black_box(name)));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn encode_names(c: &mut Criterion) {
+    let names: Vec<String> = folder_list(60_000)
+        .into_iter()
+        .map(decode_utf7_imap)
+        .collect();
+    let bytes = names.iter().map(String::len).sum::<usize>();
+    let mut group = c.benchmark_group("encode 60k folders");
+    group.throughput(Throughput::Bytes(bytes as u64));
+    group.bench_function("encode_utf7_imap", |b| {
+        b.iter(|| {
+            for name in &names {
+                black_box(encode_utf7_imap(black_box(name.clone())));
+            }
+        })
+    });
+    group.bench_function("encode", |b| {
+        b.iter(|| {
+            for name in &names {
+                black_box(encode(black_box(name)));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, decode, encode_names);
+criterion_main!(benches);
@@ -0,0 +1,125 @@
+use alloc::string::String;
+use core::fmt;
+
+use crate::{decode_utf7_imap_with, DecodeError, DecodeOptions, Mode, SurrogatePolicy};
+
+/// Rewrite UTF-7 IMAP mailbox name in the canonical form produced by [`encode_utf7_imap`]
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3501#section-5.1.3>
+///
+/// The name is decoded with [`Mode::Lenient`] and encoded again. Unpaired surrogates are
+/// kept as they are, so names that differ in them stay different.
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::canonicalize_utf7_imap;
+///
+/// assert_eq!(canonicalize_utf7_imap("th&AOk-&AOI-tre").unwrap(), "th&AOkA4g-tre");
+/// assert_eq!(canonicalize_utf7_imap("th&AOkA4g=-tre").unwrap(), "th&AOkA4g-tre");
+/// ```
+///
+/// [`encode_utf7_imap`]: crate::encode_utf7_imap
+pub fn canonicalize_utf7_imap(text: &str) -> Result<String, DecodeError> {
+    let options = DecodeOptions::new()
+        .mode(Mode::Lenient)
+        .surrogates(SurrogatePolicy::Lossless);
+    decode_utf7_imap_with(text, &options).map(|report| report.encode())
+}
+
+/// Check whether two UTF-7 IMAP mailbox names encode the same name
+///
+/// Names that cannot be canonicalized are only equal to themselves.
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::canonical_eq;
+///
+/// assert!(canonical_eq("&AOkA4g-", "&AOk-&AOI-"));
+/// assert!(!canonical_eq("&AOkA4g-", "&AOk-"));
+/// ```
+pub fn canonical_eq(a: &str, b: &str) -> bool {
+    match (canonicalize_utf7_imap(a), canonicalize_utf7_imap(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// UTF-7 IMAP mailbox name that compares and hashes by its canonical encoding
+///
+/// Use it as a map key to deduplicate folders whose names were spelled differently by
+/// different servers or clients.
+///
+/// # Usage:
+///
+/// ```
+/// use std::collections::HashSet;
+/// use utf7_imap::CanonicalName;
+///
+/// let mut folders = HashSet::new();
+/// folders.insert(CanonicalName::new("th&AOkA4g-tre").unwrap());
+/// folders.insert(CanonicalName::new("th&AOk-&AOI-tre").unwrap());
+/// assert_eq!(folders.len(), 1);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CanonicalName(String);
+
+impl CanonicalName {
+    /// Canonicalize an encoded mailbox name.
+    pub fn new(text: &str) -> Result<Self, DecodeError> {
+        canonicalize_utf7_imap(text).map(CanonicalName)
+    }
+
+    /// The canonical encoding.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Take the canonical encoding.
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl AsRef<str> for CanonicalName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CanonicalName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_forms() {
+        for spelling in [
+            "&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-",
+            "&BB4EQgQ/BEAEMAQyBDsENQQ9BD0ESwQ1-",
+            "&BB4EQgQ,BEAEMAQy-&BDsENQQ9BD0ESwQ1-",
+            "Отправленные",
+        ] {
+            assert_eq!(
+                canonicalize_utf7_imap(spelling).unwrap(),
+                "&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-",
+                "{}",
+                spelling
+            );
+        }
+        assert_eq!(
+            canonicalize_utf7_imap("Tom & Jerry").unwrap(),
+            "Tom &- Jerry"
+        );
+        assert_eq!(canonicalize_utf7_imap("&2D0-").unwrap(), "&2D0-");
+        assert!(!canonical_eq("&2D0-", "&,,0-"));
+        assert!(canonicalize_utf7_imap("&BB4EQgQ").is_err());
+        assert!(canonical_eq("&BB4EQgQ", "&BB4EQgQ"));
+    }
+}
@@ -0,0 +1,151 @@
+use alloc::string::String;
+
+use crate::wire::is_well_formed;
+use crate::{decode, decode_bytes, is_ascii_custom, is_valid_utf7_imap};
+
+/// The encoding a mailbox name appears to use, as guessed by [`classify`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameEncoding {
+    /// Printable US-ASCII without `&`, which reads the same in every encoding.
+    PlainAscii,
+    /// Well-formed modified UTF-7.
+    ModifiedUtf7,
+    /// UTF-8 that was not encoded, as sent by servers that ignore RFC 3501.
+    RawUtf8,
+    /// Bytes that are not UTF-8 and are most likely ISO-8859-1.
+    Latin1Guess,
+    /// Control characters, which no encoding allows in a name.
+    Invalid,
+}
+
+impl NameEncoding {
+    /// Decode `name` as this encoding.
+    ///
+    /// Returns `None` for [`NameEncoding::Invalid`], or if `name` is not in this encoding.
+    pub fn decode(self, name: &[u8]) -> Option<String> {
+        match self {
+            NameEncoding::PlainAscii | NameEncoding::RawUtf8 => {
+                core::str::from_utf8(name).ok().map(String::from)
+            }
+            NameEncoding::ModifiedUtf7 => decode_bytes(name).ok(),
+            NameEncoding::Latin1Guess => Some(name.iter().map(|&byte| char::from(byte)).collect()),
+            NameEncoding::Invalid => None,
+        }
+    }
+}
+
+/// Result of [`classify`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Classification {
+    /// The most likely encoding.
+    pub encoding: NameEncoding,
+    /// How likely the guess is, from 0.0 to 1.0.
+    pub confidence: f32,
+}
+
+/// Guess the encoding of a mailbox name sent by a server
+///
+/// Printable US-ASCII is checked with the same validator as [`Utf7ImapStr`]. A canonical
+/// encoding is certain, a non-canonical one less so, and a name whose decoding is itself
+/// an encoded name outside US-ASCII was most likely encoded twice, so it gets a low
+/// confidence. Printable US-ASCII that is not well-formed, such as `Tom & Jerry`, is
+/// taken to be raw text.
+///
+/// Other bytes are raw UTF-8 if they are valid UTF-8, and ISO-8859-1 otherwise, with a
+/// lower confidence if they include the C1 controls, which are more likely Windows-1252.
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::{classify, NameEncoding};
+///
+/// assert_eq!(classify(b"Archive").encoding, NameEncoding::PlainAscii);
+/// assert_eq!(classify(b"&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-").encoding, NameEncoding::ModifiedUtf7);
+/// assert_eq!(classify("Отправленные".as_bytes()).encoding, NameEncoding::RawUtf8);
+/// assert_eq!(classify(b"caf\xe9").encoding, NameEncoding::Latin1Guess);
+///
+/// let guess = classify(b"caf\xe9");
+/// assert_eq!(guess.encoding.decode(b"caf\xe9").unwrap(), "café");
+/// ```
+///
+/// [`Utf7ImapStr`]: crate::Utf7ImapStr
+pub fn classify(name: &[u8]) -> Classification {
+    let guess = |encoding, confidence| Classification {
+        encoding,
+        confidence,
+    };
+    if name.iter().any(|&byte| byte.is_ascii_control()) {
+        return guess(NameEncoding::Invalid, 1.0);
+    }
+    if name.iter().all(|&byte| is_ascii_custom(byte)) {
+        if !name.contains(&b'&') {
+            return guess(NameEncoding::PlainAscii, 1.0);
+        }
+        if !is_well_formed(name) {
+            return guess(NameEncoding::RawUtf8, 0.6);
+        }
+        // Printable US-ASCII is UTF-8.
+        let text = core::str::from_utf8(name).unwrap_or_default();
+        if is_double_encoded(text) {
+            return guess(NameEncoding::ModifiedUtf7, 0.5);
+        }
+        let confidence = if is_valid_utf7_imap(text) { 1.0 } else { 0.8 };
+        return guess(NameEncoding::ModifiedUtf7, confidence);
+    }
+    if core::str::from_utf8(name).is_ok() {
+        return guess(NameEncoding::RawUtf8, 0.95);
+    }
+    let c1_controls = name.iter().any(|byte| (0x80..0xa0).contains(byte));
+    guess(
+        NameEncoding::Latin1Guess,
+        if c1_controls { 0.3 } else { 0.6 },
+    )
+}
+
+/// Whether decoding `text` gives an encoded name that decodes outside US-ASCII.
+fn is_double_encoded(text: &str) -> bool {
+    match decode(text) {
+        Ok(once) => once.contains('&') && decode(&once).is_ok_and(|twice| !twice.is_ascii()),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode;
+
+    #[test]
+    fn modified_utf7_confidence() {
+        let confidence = |name: &str| {
+            let guess = classify(name.as_bytes());
+            assert_eq!(guess.encoding, NameEncoding::ModifiedUtf7, "{}", name);
+            guess.confidence
+        };
+        assert_eq!(confidence("th&AOkA4g-tre"), 1.0);
+        assert_eq!(confidence("Tom &- Jerry"), 1.0);
+        assert_eq!(confidence("th&AOk-&AOI-tre"), 0.8);
+
+        let twice = encode(&encode("Отправленные")).into_owned();
+        assert_eq!(twice, "&-BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-");
+        assert_eq!(confidence(&twice), 0.5);
+    }
+
+    #[test]
+    fn other_encodings() {
+        let guess = |name: &[u8]| {
+            let guess = classify(name);
+            (guess.encoding, guess.confidence)
+        };
+        assert_eq!(guess(b""), (NameEncoding::PlainAscii, 1.0));
+        assert_eq!(guess(b"Tom & Jerry"), (NameEncoding::RawUtf8, 0.6));
+        assert_eq!(guess("caf\u{e9}".as_bytes()), (NameEncoding::RawUtf8, 0.95));
+        assert_eq!(guess(b"\x93quoted\x94"), (NameEncoding::Latin1Guess, 0.3));
+        assert_eq!(guess(b"a\tb"), (NameEncoding::Invalid, 1.0));
+        assert_eq!(NameEncoding::Invalid.decode(b"a\tb"), None);
+        assert_eq!(
+            NameEncoding::ModifiedUtf7.decode(b"&AOk-").unwrap(),
+            "\u{e9}"
+        );
+    }
+}
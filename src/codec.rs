@@ -0,0 +1,166 @@
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::command::argument;
+use crate::{decode, decode_bytes, encode, DecodeError, LiteralSupport};
+
+/// How mailbox names travel on a connection
+///
+/// Names are modified UTF-7 until the client has enabled `UTF8=ACCEPT`
+/// ([RFC 6855](https://datatracker.ietf.org/doc/html/rfc6855)) or `IMAP4rev2`
+/// ([RFC 9051](https://datatracker.ietf.org/doc/html/rfc9051)), and raw UTF-8 after that.
+/// Keep one codec per connection and replace it once the server's `ENABLED` response
+/// has been read.
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::MailboxCodec;
+///
+/// let mut codec = MailboxCodec::default();
+/// assert_eq!(codec.encode("Отправленные"), "&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-");
+///
+/// codec = MailboxCodec::from_enabled(["UTF8=ACCEPT"]);
+/// assert_eq!(codec.encode("Отправленные"), "Отправленные");
+/// assert_eq!(codec.decode("Отправленные").unwrap(), "Отправленные");
+///
+/// // names created before the switch still decode
+/// assert_eq!(codec.decode("&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-").unwrap(), "Отправленные");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MailboxCodec {
+    /// Modified UTF-7, as required by RFC 3501.
+    #[default]
+    ModifiedUtf7,
+    /// Raw UTF-8, after `ENABLE UTF8=ACCEPT` or `ENABLE IMAP4rev2`.
+    Utf8,
+}
+
+impl MailboxCodec {
+    /// Pick the codec for the capabilities listed in the server's `ENABLED` response.
+    pub fn from_enabled<'a>(enabled: impl IntoIterator<Item = &'a str>) -> Self {
+        let utf8 = enabled.into_iter().any(|capability| {
+            capability.eq_ignore_ascii_case("UTF8=ACCEPT")
+                || capability.eq_ignore_ascii_case("IMAP4rev2")
+        });
+        if utf8 {
+            MailboxCodec::Utf8
+        } else {
+            MailboxCodec::ModifiedUtf7
+        }
+    }
+
+    /// Encode a mailbox name, borrowing it if it needs no encoding.
+    pub fn encode<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        match self {
+            MailboxCodec::ModifiedUtf7 => encode(name),
+            MailboxCodec::Utf8 => Cow::Borrowed(name),
+        }
+    }
+
+    /// Decode a mailbox name sent by the server.
+    ///
+    /// With [`MailboxCodec::Utf8`] this never fails. A name that is printable US-ASCII and
+    /// decodes as modified UTF-7 to something outside US-ASCII is taken to be a legacy
+    /// name and decoded, while any other name is kept as it is.
+    pub fn decode<'a>(&self, name: &'a str) -> Result<Cow<'a, str>, DecodeError> {
+        match self {
+            MailboxCodec::ModifiedUtf7 => decode(name),
+            MailboxCodec::Utf8 => Ok(decode_legacy(name).map_or(Cow::Borrowed(name), Cow::Owned)),
+        }
+    }
+
+    /// Decode a mailbox name from raw IMAP wire data.
+    ///
+    /// With [`MailboxCodec::Utf8`] the name has to be valid UTF-8.
+    pub fn decode_bytes(&self, name: &[u8]) -> Result<String, DecodeError> {
+        match self {
+            MailboxCodec::ModifiedUtf7 => decode_bytes(name),
+            MailboxCodec::Utf8 => match core::str::from_utf8(name) {
+                Ok(name) => Ok(decode_legacy(name).unwrap_or_else(|| String::from(name))),
+                Err(err) => Err(DecodeError::unshifted_byte(name, err.valid_up_to())),
+            },
+        }
+    }
+
+    /// Turn a mailbox name into a command argument, as [`mailbox_argument`] does.
+    ///
+    /// With [`MailboxCodec::Utf8`] a name outside US-ASCII is sent as a quoted string, and
+    /// only needs a literal if it holds CR, LF or NUL.
+    ///
+    /// [`mailbox_argument`]: crate::mailbox_argument
+    pub fn argument(&self, name: &str, literals: LiteralSupport) -> Vec<u8> {
+        if name.eq_ignore_ascii_case("INBOX") {
+            return b"INBOX".to_vec();
+        }
+        let utf8 = *self == MailboxCodec::Utf8;
+        argument(self.encode(name).as_bytes(), utf8, literals)
+    }
+}
+
+/// The decoding of a name sent in modified UTF-7 although UTF-8 is enabled.
+fn decode_legacy(name: &str) -> Option<String> {
+    if !name.contains('&') {
+        return None;
+    }
+    match decode(name) {
+        Ok(Cow::Owned(decoded)) if !decoded.is_ascii() => Some(decoded),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DecodeErrorKind;
+
+    #[test]
+    fn utf8_keeps_names() {
+        let codec = MailboxCodec::Utf8;
+        // `&` is an ordinary character in UTF-8 names
+        assert_eq!(codec.encode("Tom & Jerry"), "Tom & Jerry");
+        assert_eq!(codec.decode("Tom & Jerry").unwrap(), "Tom & Jerry");
+        assert_eq!(codec.decode("Tom &- Jerry").unwrap(), "Tom &- Jerry");
+        assert_eq!(codec.decode("&AOk").unwrap(), "&AOk");
+        assert_eq!(codec.decode_bytes("café".as_bytes()).unwrap(), "café");
+        assert_eq!(codec.decode_bytes(b"caf&AOk-").unwrap(), "café");
+        assert_eq!(
+            codec.decode_bytes(b"caf\xe9").map_err(|err| err.kind()),
+            Err(DecodeErrorKind::UnshiftedByte(0xe9))
+        );
+        let err = codec.decode_bytes(b"caf\xe9x").unwrap_err();
+        assert_eq!(
+            err.snippet().to_string(),
+            "byte 0xe9 is not printable US-ASCII at byte 3\n  caf\u{fffd}x\n     ^"
+        );
+    }
+
+    #[test]
+    fn codec_selection() {
+        assert_eq!(
+            MailboxCodec::from_enabled(["CONDSTORE", "imap4rev2"]),
+            MailboxCodec::Utf8
+        );
+        assert_eq!(
+            MailboxCodec::from_enabled(["CONDSTORE"]),
+            MailboxCodec::ModifiedUtf7
+        );
+
+        let argument = |codec: MailboxCodec| codec.argument("café", LiteralSupport::Plus);
+        assert_eq!(argument(MailboxCodec::ModifiedUtf7), b"caf&AOk-");
+        assert_eq!(argument(MailboxCodec::Utf8), "\"café\"".as_bytes());
+        assert_eq!(
+            MailboxCodec::Utf8.argument("Входящие \"2024\"", LiteralSupport::Synchronizing),
+            "\"Входящие \\\"2024\\\"\"".as_bytes()
+        );
+        assert_eq!(
+            MailboxCodec::Utf8.argument("café\r\n", LiteralSupport::Plus),
+            "{7+}\r\ncafé\r\n".as_bytes()
+        );
+        assert_eq!(
+            MailboxCodec::Utf8.argument("inbox", LiteralSupport::Plus),
+            b"INBOX"
+        );
+    }
+}
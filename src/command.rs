@@ -0,0 +1,150 @@
+use alloc::vec::Vec;
+
+use crate::MailboxCodec;
+
+/// Size limit of a non-synchronizing literal under LITERAL-.
+const LITERAL_MINUS_MAX: usize = 4096;
+
+/// Non-synchronizing literals the server accepts, from its `CAPABILITY` response
+///
+/// <https://datatracker.ietf.org/doc/html/rfc7888>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LiteralSupport {
+    /// Only `{n}` literals, after each of which the server's `+` has to be awaited.
+    #[default]
+    Synchronizing,
+    /// `LITERAL+`: `{n+}` literals of any size.
+    Plus,
+    /// `LITERAL-`: `{n+}` literals of up to 4096 bytes.
+    Minus,
+}
+
+/// Turn a mailbox name into a command argument for SELECT, CREATE, RENAME, APPEND, COPY,
+/// MOVE, STATUS and the like
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3501#section-9>
+///
+/// The name is encoded and sent as an atom if it can be, and as a quoted string
+/// otherwise. `INBOX` is sent in upper case whatever case it is given in.
+///
+/// An encoded name is printable US-ASCII, which always fits in a quoted string, so
+/// `literals` only matters for the bytes given to [`astring_argument`]. A `{n}` literal is
+/// synchronizing: send the bytes up to and including its CRLF, and the rest once the
+/// server has answered with `+`.
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::{mailbox_argument, LiteralSupport};
+///
+/// assert_eq!(mailbox_argument("Отправленные", LiteralSupport::Plus), b"&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-");
+/// assert_eq!(mailbox_argument("Tom & Jerry", LiteralSupport::Plus), br#""Tom &- Jerry""#);
+/// assert_eq!(mailbox_argument("inbox", LiteralSupport::Plus), b"INBOX");
+/// ```
+pub fn mailbox_argument(name: &str, literals: LiteralSupport) -> Vec<u8> {
+    MailboxCodec::ModifiedUtf7.argument(name, literals)
+}
+
+/// Turn an already encoded name, or any other IMAP `astring`, into a command argument
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3501#section-9>
+///
+/// The bytes are sent as an atom if they can be, as a quoted string with `"` and `\`
+/// escaped if they are US-ASCII without CR and LF, and as a literal otherwise.
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::{astring_argument, LiteralSupport};
+///
+/// assert_eq!(astring_argument(b"a\"b", LiteralSupport::Synchronizing), br#""a\"b""#);
+/// assert_eq!(astring_argument("café".as_bytes(), LiteralSupport::Synchronizing), "{5}\r\ncafé".as_bytes());
+/// assert_eq!(astring_argument("café".as_bytes(), LiteralSupport::Minus), "{5+}\r\ncafé".as_bytes());
+/// ```
+pub fn astring_argument(bytes: &[u8], literals: LiteralSupport) -> Vec<u8> {
+    argument(bytes, false, literals)
+}
+
+/// [`astring_argument`], also quoting bytes outside US-ASCII if `utf8` is set, as RFC 6855
+/// and RFC 9051 allow once UTF-8 is enabled.
+pub(crate) fn argument(bytes: &[u8], utf8: bool, literals: LiteralSupport) -> Vec<u8> {
+    if !bytes.is_empty() && bytes.iter().all(|&byte| is_astring_char(byte)) {
+        return bytes.to_vec();
+    }
+    if bytes
+        .iter()
+        .all(|&byte| is_text_char(byte) || utf8 && !byte.is_ascii())
+    {
+        let mut quoted = Vec::with_capacity(bytes.len() + 2);
+        quoted.push(b'"');
+        for &byte in bytes {
+            if matches!(byte, b'"' | b'\\') {
+                quoted.push(b'\\');
+            }
+            quoted.push(byte);
+        }
+        quoted.push(b'"');
+        return quoted;
+    }
+    let non_synchronizing = match literals {
+        LiteralSupport::Synchronizing => false,
+        LiteralSupport::Plus => true,
+        LiteralSupport::Minus => bytes.len() <= LITERAL_MINUS_MAX,
+    };
+    let header = if non_synchronizing {
+        alloc::format!("{{{}+}}\r\n", bytes.len())
+    } else {
+        alloc::format!("{{{}}}\r\n", bytes.len())
+    };
+    let mut literal = Vec::with_capacity(header.len() + bytes.len());
+    literal.extend_from_slice(header.as_bytes());
+    literal.extend_from_slice(bytes);
+    literal
+}
+
+/// `ASTRING-CHAR`: any printable US-ASCII except `(`, `)`, `{`, space, `%`, `*`, `"` and `\`.
+fn is_astring_char(byte: u8) -> bool {
+    matches!(byte, 0x21..=0x7e) && !matches!(byte, b'(' | b')' | b'{' | b'%' | b'*' | b'"' | b'\\')
+}
+
+/// `TEXT-CHAR`: any US-ASCII except NUL, CR and LF.
+fn is_text_char(byte: u8) -> bool {
+    byte.is_ascii() && !matches!(byte, b'\0' | b'\r' | b'\n')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn argument_forms() {
+        let arg = |name| mailbox_argument(name, LiteralSupport::Synchronizing);
+        assert_eq!(arg("Archive/2024"), b"Archive/2024");
+        assert_eq!(arg("INBOX.Sent"), b"INBOX.Sent");
+        assert_eq!(arg("InBox"), b"INBOX");
+        assert_eq!(arg(""), br#""""#);
+        assert_eq!(arg("50% off"), br#""50% off""#);
+        assert_eq!(arg("[Gmail]/All Mail"), br#""[Gmail]/All Mail""#);
+        assert_eq!(arg("[Gmail]/Sent"), b"[Gmail]/Sent");
+        assert_eq!(arg(r#"say "hi" \o/"#), br#""say \"hi\" \\o/""#);
+        // control characters are encoded, so never need a literal
+        assert_eq!(arg("a\r\nb"), b"a&AA0ACg-b");
+    }
+
+    #[test]
+    fn literals() {
+        let small = b"a\nb";
+        assert_eq!(
+            astring_argument(small, LiteralSupport::Plus),
+            b"{3+}\r\na\nb"
+        );
+        assert_eq!(
+            astring_argument(small, LiteralSupport::Minus),
+            b"{3+}\r\na\nb"
+        );
+
+        let large = [0xff; LITERAL_MINUS_MAX + 1];
+        assert!(astring_argument(&large, LiteralSupport::Minus).starts_with(b"{4097}\r\n"));
+        assert!(astring_argument(&large, LiteralSupport::Plus).starts_with(b"{4097+}\r\n"));
+    }
+}
@@ -0,0 +1,448 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use core::fmt::Write;
+
+use crate::{
+    is_ascii_custom, is_ascii_custom_char, BufferTooSmall, DecodeError, DecodeErrorKind,
+    DecodeOptions, DecodeReport, DecodeSliceError, Mode, Repair, RepairKind, SliceWriter,
+    SurrogatePolicy, Unterminated, INVALID_BASE64, MODIFIED_BASE64_VALUES,
+};
+
+/// Single-pass decoder behind [`crate::decode_utf7_imap_with`] and
+/// [`crate::decode_utf7_imap`]
+///
+/// Unshifted text is copied in runs up to the next `&`. Each shift sequence is decoded
+/// one modified BASE64 character at a time into `shifted`, which is reused for every
+/// sequence of the name.
+struct Decoder<'a> {
+    text: &'a str,
+    options: &'a DecodeOptions,
+    result: String,
+    utf16: Option<Vec<u16>>,
+    repairs: Vec<Repair>,
+    truncated: bool,
+    /// UTF-16 code units of the current shift sequence.
+    units: Vec<u16>,
+}
+
+/// How a shift sequence ended.
+struct Shift {
+    /// From the `&` to the closing `-`, if there is one.
+    whole: Range<usize>,
+    /// The sequence held at least one modified BASE64 character.
+    encoded: bool,
+}
+
+pub(crate) fn decode(text: &str, options: &DecodeOptions) -> Result<DecodeReport, DecodeError> {
+    let mut decoder = Decoder::new(text, options);
+    let mut last = 0;
+    // End of the previous shift sequence, if it held any modified BASE64.
+    let mut after_shift = None;
+    while let Some(start) = decoder.find_shift(last) {
+        decoder.unshifted(last..start)?;
+        let shift = decoder.shift(start, after_shift == Some(start))?;
+        after_shift = shift.encoded.then_some(shift.whole.end);
+        last = shift.whole.end;
+    }
+    decoder.unshifted(last..text.len())?;
+    Ok(decoder.finish())
+}
+
+/// Decode every well-formed shift sequence and keep the others as they are.
+///
+/// Unpaired surrogates are replaced rather than kept, as `decode_utf7_imap` always did.
+pub(crate) fn decode_passthrough(text: &str) -> String {
+    let options = DecodeOptions::new().surrogates(SurrogatePolicy::Replace);
+    let mut decoder = Decoder::new(text, &options);
+    let mut last = 0;
+    while let Some(start) = decoder.find_shift(last) {
+        decoder.result.push_str(&text[last..start]);
+        last = match decoder.shift(start, false) {
+            Ok(shift) => shift.whole.end,
+            Err(_) => {
+                let end = decoder.sequence_end(start);
+                decoder.result.push_str(&text[start..end]);
+                end
+            }
+        };
+    }
+    decoder.result.push_str(&text[last..]);
+    decoder.result
+}
+
+pub(crate) fn decode_into_slice(src: &[u8], dst: &mut [u8]) -> Result<usize, DecodeSliceError> {
+    let mut out = SliceWriter {
+        buffer: dst,
+        len: 0,
+    };
+    decode_into(src, &mut out)?;
+    Ok(out.len)
+}
+
+/// Decode raw bytes into `out` with the checks of [`decode`] in [`Mode::Standard`], without
+/// allocating.
+///
+/// Code units are written as soon as they are complete, so an unpaired surrogate is
+/// remembered and only reported once the sequence has passed the other checks. After a
+/// failed write nothing more is written, but the rest of the input is still checked, so
+/// that a malformed input is reported as such rather than as
+/// [`DecodeSliceError::BufferTooSmall`].
+pub(crate) fn decode_into<W: Write + ?Sized>(
+    src: &[u8],
+    out: &mut W,
+) -> Result<(), DecodeSliceError> {
+    let invalid = |kind, position| DecodeSliceError::Invalid { kind, position };
+    if let Some(position) = src.iter().position(|&byte| !is_ascii_custom(byte)) {
+        return Err(invalid(
+            DecodeErrorKind::UnshiftedByte(src[position]),
+            position,
+        ));
+    }
+    let mut full = false;
+    let mut write = |c: char| full = full || out.write_char(c).is_err();
+    let mut i = 0;
+    while i < src.len() {
+        if src[i] != b'&' {
+            write(char::from(src[i]));
+            i += 1;
+            continue;
+        }
+        let start = i;
+        i += 1;
+        let mut bits = 0;
+        let mut buffer = 0;
+        let mut index = 0;
+        let mut high = None;
+        let mut unpaired = None;
+        loop {
+            let Some(&byte) = src.get(i) else {
+                return Err(invalid(DecodeErrorKind::UnterminatedShift, start));
+            };
+            if byte == b'-' {
+                break;
+            }
+            let value = match MODIFIED_BASE64_VALUES[usize::from(byte)] {
+                INVALID_BASE64 if !src[i..].contains(&b'-') => {
+                    return Err(invalid(DecodeErrorKind::UnterminatedShift, start));
+                }
+                INVALID_BASE64 => {
+                    let kind = DecodeErrorKind::InvalidBase64Char(char::from(byte));
+                    return Err(invalid(kind, i));
+                }
+                value => u32::from(value),
+            };
+            buffer = (buffer << 6) | value;
+            bits += 6;
+            i += 1;
+            if bits < 16 {
+                continue;
+            }
+            bits -= 16;
+            let unit = (buffer >> bits) as u16;
+            buffer &= (1 << bits) - 1;
+            match (unit, high) {
+                (0xd800..=0xdbff, None) => high = Some((index, unit)),
+                (0xdc00..=0xdfff, Some((_, high_unit))) => {
+                    high = None;
+                    write(surrogate_pair(high_unit, unit));
+                }
+                (_, Some(high)) => {
+                    unpaired.get_or_insert(high);
+                }
+                (0xdc00..=0xdfff, None) => {
+                    unpaired.get_or_insert((index, unit));
+                }
+                _ => write(char::from_u32(u32::from(unit)).unwrap_or_default()),
+            }
+            index += 1;
+        }
+        let end = i;
+        i += 1;
+        if end == start + 1 {
+            write('&');
+            continue;
+        }
+        // The bits after the last whole byte must be fewer than six and all zero, and
+        // there must be no whole byte left over.
+        let byte_bits = bits % 8;
+        if byte_bits >= 6 || buffer & ((1 << byte_bits) - 1) != 0 {
+            return Err(invalid(DecodeErrorKind::BadPadding, end - 1));
+        }
+        if bits >= 8 {
+            return Err(invalid(DecodeErrorKind::OddByteCount, end - 1));
+        }
+        if let Some((index, unit)) = unpaired.or(high) {
+            return Err(invalid(
+                DecodeErrorKind::UnpairedSurrogate(unit),
+                start + 1 + index * 16 / 6,
+            ));
+        }
+    }
+    if full {
+        return Err(BufferTooSmall.into());
+    }
+    Ok(())
+}
+
+impl<'a> Decoder<'a> {
+    fn new(text: &'a str, options: &'a DecodeOptions) -> Self {
+        Decoder {
+            text,
+            options,
+            result: String::with_capacity(text.len()),
+            utf16: (options.surrogates == SurrogatePolicy::Lossless).then(Vec::new),
+            repairs: Vec::new(),
+            truncated: false,
+            units: Vec::new(),
+        }
+    }
+
+    fn finish(self) -> DecodeReport {
+        DecodeReport {
+            text: self.result,
+            truncated: self.truncated,
+            utf16: self.utf16,
+            repairs: self.repairs,
+        }
+    }
+
+    fn error(&self, kind: DecodeErrorKind, sequence: Range<usize>, position: usize) -> DecodeError {
+        DecodeError::new(kind, self.text, sequence, position)
+    }
+
+    fn find_shift(&self, from: usize) -> Option<usize> {
+        let offset = self.text.as_bytes()[from..]
+            .iter()
+            .position(|&byte| byte == b'&')?;
+        Some(from + offset)
+    }
+
+    /// End of the shift sequence starting at `start` as RFC 3501 delimits it: just past
+    /// the next `-`, or the end of the input.
+    fn sequence_end(&self, start: usize) -> usize {
+        self.text.as_bytes()[start..]
+            .iter()
+            .position(|&byte| byte == b'-')
+            .map_or(self.text.len(), |len| start + len + 1)
+    }
+
+    fn push_ampersand(&mut self) {
+        self.result.push('&');
+        if let Some(units) = &mut self.utf16 {
+            units.push(u16::from(b'&'));
+        }
+    }
+
+    fn unshifted(&mut self, range: Range<usize>) -> Result<(), DecodeError> {
+        let unshifted = &self.text[range.clone()];
+        if self.options.mode != Mode::Standard {
+            for (i, c) in unshifted.char_indices() {
+                let position = range.start + i;
+                let span = position..position + c.len_utf8();
+                match self.options.mode {
+                    Mode::Strict if !is_ascii_custom_char(c) => {
+                        return Err(self.error(DecodeErrorKind::UnshiftedChar(c), span, position));
+                    }
+                    Mode::Lenient if !c.is_ascii() => self.repairs.push(Repair {
+                        kind: RepairKind::RawNonAscii,
+                        span,
+                    }),
+                    _ => {}
+                }
+            }
+        }
+        self.result.push_str(unshifted);
+        if let Some(units) = &mut self.utf16 {
+            units.extend(unshifted.encode_utf16());
+        }
+        Ok(())
+    }
+
+    fn repair(&mut self, kind: RepairKind, span: &Range<usize>) {
+        self.repairs.push(Repair {
+            kind,
+            span: span.clone(),
+        });
+    }
+
+    /// Decode the shift sequence whose `&` is at `start`, which directly follows another
+    /// one if `after_shift` is set.
+    ///
+    /// Nothing is written to the result unless the whole sequence decodes.
+    fn shift(&mut self, start: usize, after_shift: bool) -> Result<Shift, DecodeError> {
+        let bytes = self.text.as_bytes();
+        let lenient = self.options.mode == Mode::Lenient;
+        let part_start = start + 1;
+        if self.options.mode == Mode::Strict
+            && after_shift
+            && bytes.get(part_start).is_some_and(|&byte| byte != b'-')
+        {
+            let whole = start..self.sequence_end(start);
+            return Err(self.error(DecodeErrorKind::AdjacentShifts, whole, start));
+        }
+        let mut i = part_start;
+        let mut bits = 0;
+        let mut buffer = 0;
+        let mut slash = false;
+        let mut padding = false;
+        self.units.clear();
+        let terminated = loop {
+            let Some(&byte) = bytes.get(i) else {
+                break false;
+            };
+            let value = match (MODIFIED_BASE64_VALUES[usize::from(byte)], byte) {
+                (_, b'-') => break true,
+                (INVALID_BASE64, b'/') if lenient => {
+                    slash = true;
+                    63
+                }
+                (INVALID_BASE64, b'=') if lenient => {
+                    // Padding can only end a sequence, so whatever follows it does too.
+                    padding = true;
+                    while bytes.get(i) == Some(&b'=') {
+                        i += 1;
+                    }
+                    break bytes.get(i) == Some(&b'-');
+                }
+                (INVALID_BASE64, _) if lenient => break false,
+                (INVALID_BASE64, _) => return Err(self.invalid_char(start, i)),
+                (value, _) => value,
+            };
+            buffer = (buffer << 6) | u32::from(value);
+            bits += 6;
+            if bits >= 16 {
+                bits -= 16;
+                self.units.push((buffer >> bits) as u16);
+                buffer &= (1 << bits) - 1;
+            }
+            i += 1;
+        };
+        let whole = start..i + usize::from(terminated);
+        let encoded = i > part_start;
+        if lenient && !encoded && !terminated {
+            self.repair(RepairKind::UnescapedAmpersand, &whole);
+            self.push_ampersand();
+            return Ok(Shift { whole, encoded });
+        }
+        if !terminated && i < bytes.len() {
+            self.repair(RepairKind::MissingTerminator, &whole);
+        } else if !terminated {
+            if self.options.unterminated == Unterminated::Error {
+                return Err(self.error(DecodeErrorKind::UnterminatedShift, whole, start));
+            }
+            self.truncated = true;
+        }
+        if !encoded {
+            if terminated {
+                self.push_ampersand();
+            }
+            return Ok(Shift { whole, encoded });
+        }
+        if slash {
+            self.repair(RepairKind::SlashInBase64, &whole);
+        }
+        if padding {
+            self.repair(RepairKind::PaddingCharacters, &whole);
+        }
+        if !terminated {
+            // Keep only the code units that were sent completely.
+            if let Some(0xd800..=0xdbff) = self.units.last() {
+                self.units.pop();
+            }
+        } else {
+            // The bits after the last whole byte must be fewer than six and all zero, and
+            // there must be no whole byte left over.
+            let byte_bits = bits % 8;
+            let bad_padding = byte_bits >= 6 || buffer & ((1 << byte_bits) - 1) != 0;
+            let odd = bits >= 8;
+            if lenient {
+                if bad_padding {
+                    self.repair(RepairKind::NonZeroPadding, &whole);
+                }
+                if odd {
+                    self.repair(RepairKind::IncompleteCodeUnit, &whole);
+                }
+            } else if bad_padding {
+                return Err(self.error(DecodeErrorKind::BadPadding, whole, i - 1));
+            } else if odd {
+                return Err(self.error(DecodeErrorKind::OddByteCount, whole, i - 1));
+            }
+        }
+        let unit_error = |kind, index| self.error(kind, whole.clone(), part_start + index * 16 / 6);
+        if self.options.surrogates == SurrogatePolicy::Error {
+            if let Some((index, unit)) = find_unpaired_surrogate(&self.units) {
+                return Err(unit_error(DecodeErrorKind::UnpairedSurrogate(unit), index));
+            }
+        }
+        if self.options.mode == Mode::Strict {
+            let shifted_ascii = self
+                .units
+                .iter()
+                .position(|&unit| u8::try_from(unit).is_ok_and(is_ascii_custom));
+            if let Some(index) = shifted_ascii {
+                let kind = DecodeErrorKind::ShiftedAscii(self.units[index] as u8 as char);
+                return Err(unit_error(kind, index));
+            }
+        }
+        if let Some(utf16) = &mut self.utf16 {
+            utf16.extend_from_slice(&self.units);
+        }
+        push_utf16(&mut self.result, &self.units);
+        Ok(Shift { whole, encoded })
+    }
+
+    /// Error for a character outside modified BASE64 at `position`, inside the shift
+    /// sequence starting at `start`.
+    ///
+    /// A sequence that is never closed is reported as such, whatever it contains.
+    fn invalid_char(&self, start: usize, position: usize) -> DecodeError {
+        let end = self.sequence_end(start);
+        let unterminated = !self.text[..end].ends_with('-');
+        if unterminated && self.options.unterminated == Unterminated::Error {
+            return self.error(DecodeErrorKind::UnterminatedShift, start..end, start);
+        }
+        let c = self.text[position..].chars().next().unwrap_or_default();
+        self.error(DecodeErrorKind::InvalidBase64Char(c), start..end, position)
+    }
+}
+
+/// Append UTF-16 code units, replacing unpaired surrogates with U+FFFD.
+fn push_utf16(result: &mut String, units: &[u16]) {
+    let mut i = 0;
+    while let Some(&unit) = units.get(i) {
+        i += 1;
+        let c = match (unit, units.get(i)) {
+            (0xd800..=0xdbff, Some(&low @ 0xdc00..=0xdfff)) => {
+                i += 1;
+                surrogate_pair(unit, low)
+            }
+            (0xd800..=0xdfff, _) => char::REPLACEMENT_CHARACTER,
+            _ => char::from_u32(u32::from(unit)).unwrap_or(char::REPLACEMENT_CHARACTER),
+        };
+        result.push(c);
+    }
+}
+
+/// The character encoded by a high and a low surrogate.
+pub(crate) fn surrogate_pair(high: u16, low: u16) -> char {
+    let c = 0x10000 + ((u32::from(high) - 0xd800) << 10) + (u32::from(low) - 0xdc00);
+    char::from_u32(c).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+/// Index and value of the first UTF-16 code unit that is a surrogate without its other
+/// half.
+fn find_unpaired_surrogate(units: &[u16]) -> Option<(usize, u16)> {
+    let mut high = None;
+    for (i, &unit) in units.iter().enumerate() {
+        match (unit, high) {
+            (0xd800..=0xdbff, None) => high = Some((i, unit)),
+            (0xdc00..=0xdfff, Some(_)) => high = None,
+            (_, Some(_)) => return high,
+            (0xdc00..=0xdfff, None) => return Some((i, unit)),
+            _ => {}
+        }
+    }
+    high
+}
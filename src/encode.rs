@@ -0,0 +1,186 @@
+use core::fmt;
+
+use crate::{is_ascii_custom, is_ascii_custom_char, MODIFIED_BASE64};
+
+/// Single-pass encoder behind [`crate::encode_to_fmt`]
+///
+/// Runs of printable US-ASCII are found a word at a time and written as slices of the
+/// input. Everything else is written as modified BASE64 while it is read.
+pub(crate) fn encode<W: fmt::Write + ?Sized>(text: &str, out: &mut W) -> fmt::Result {
+    let bytes = text.as_bytes();
+    // Start of the printable US-ASCII not written yet.
+    let mut start = 0;
+    let mut i = 0;
+    loop {
+        i += literal_len(&bytes[i..]);
+        match bytes.get(i) {
+            None => break,
+            Some(b'&') => {
+                i += 1;
+                out.write_str(&text[start..i])?;
+                out.write_char('-')?;
+                start = i;
+            }
+            Some(_) => {
+                out.write_str(&text[start..i])?;
+                let mut shift = ShiftWriter::new(out)?;
+                let mut units = [0; 2];
+                for c in text[i..].chars() {
+                    if is_ascii_custom_char(c) {
+                        break;
+                    }
+                    for &unit in c.encode_utf16(&mut units).iter() {
+                        shift.push(unit)?;
+                    }
+                    i += c.len_utf8();
+                }
+                shift.finish()?;
+                start = i;
+            }
+        }
+    }
+    out.write_str(&text[start..])
+}
+
+/// Length of the encoding of `text`.
+pub(crate) fn encoded_len(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    let mut len = 0;
+    let mut i = 0;
+    loop {
+        let literal = literal_len(&bytes[i..]);
+        len += literal;
+        i += literal;
+        match bytes.get(i) {
+            None => return len,
+            Some(b'&') => {
+                len += 2;
+                i += 1;
+            }
+            Some(_) => {
+                let mut units = 0;
+                for c in text[i..].chars() {
+                    if is_ascii_custom_char(c) {
+                        break;
+                    }
+                    units += c.len_utf16();
+                    i += c.len_utf8();
+                }
+                len += shift_len(units);
+            }
+        }
+    }
+}
+
+/// Length of a shift sequence holding `units` UTF-16 code units.
+fn shift_len(units: usize) -> usize {
+    2 + (units * 16).div_ceil(6)
+}
+
+/// Printable US-ASCII other than `&`, the bytes copied to the output unchanged.
+fn is_literal(byte: u8) -> bool {
+    byte != b'&' && is_ascii_custom(byte)
+}
+
+/// Length of the run of literal bytes at the start of `bytes`.
+pub(crate) fn literal_len(bytes: &[u8]) -> usize {
+    const LANES: usize = 16;
+    let word = |bytes: &[u8]| u64::from_ne_bytes(bytes.try_into().unwrap_or_default());
+    let mut len = 0;
+    for chunk in bytes.chunks_exact(LANES) {
+        let (low, high) = chunk.split_at(LANES / 2);
+        if !is_literal_word(word(low)) || !is_literal_word(word(high)) {
+            break;
+        }
+        len += LANES;
+    }
+    len + bytes[len..]
+        .iter()
+        .position(|&byte| !is_literal(byte))
+        .unwrap_or(bytes.len() - len)
+}
+
+/// Whether all eight bytes of `word` are literal, tested without looking at each byte.
+fn is_literal_word(word: u64) -> bool {
+    const ONES: u64 = u64::from_ne_bytes([0x01; 8]);
+    const HIGH: u64 = ONES * 0x80;
+    // Set in a byte's high bit when the byte is below `n`, for `n` up to 0x80.
+    let below = |word: u64, n: u64| word.wrapping_sub(ONES * n) & !word & HIGH;
+    let non_ascii = word & HIGH;
+    let control = below(word, 0x20);
+    let delete = below(word ^ (ONES * 0x7f), 1);
+    let ampersand = below(word ^ (ONES * u64::from(b'&')), 1);
+    non_ascii | control | delete | ampersand == 0
+}
+
+/// Write a shift sequence holding the given UTF-16 code units.
+pub(crate) fn write_shift<W: fmt::Write + ?Sized>(
+    units: impl Iterator<Item = u16>,
+    out: &mut W,
+) -> fmt::Result {
+    let mut shift = ShiftWriter::new(out)?;
+    for unit in units {
+        shift.push(unit)?;
+    }
+    shift.finish()
+}
+
+/// Writes a shift sequence, collecting its modified BASE64 on the stack.
+struct ShiftWriter<'a, W: ?Sized> {
+    out: &'a mut W,
+    chunk: [u8; 64],
+    len: usize,
+    /// Bits not yet written as a modified BASE64 character.
+    bits: u32,
+    buffer: u32,
+}
+
+impl<'a, W: fmt::Write + ?Sized> ShiftWriter<'a, W> {
+    /// Start a shift sequence by writing `&`.
+    fn new(out: &'a mut W) -> Result<Self, fmt::Error> {
+        out.write_char('&')?;
+        Ok(ShiftWriter {
+            out,
+            chunk: [0; 64],
+            len: 0,
+            bits: 0,
+            buffer: 0,
+        })
+    }
+
+    fn push(&mut self, unit: u16) -> fmt::Result {
+        self.buffer = (self.buffer << 16) | u32::from(unit);
+        self.bits += 16;
+        while self.bits >= 6 {
+            self.bits -= 6;
+            self.push_sextet(self.buffer >> self.bits)?;
+            self.buffer &= (1 << self.bits) - 1;
+        }
+        Ok(())
+    }
+
+    /// Write the remaining bits, padded with zeros, and the closing `-`.
+    fn finish(mut self) -> fmt::Result {
+        if self.bits > 0 {
+            self.push_sextet(self.buffer << (6 - self.bits))?;
+        }
+        self.flush()?;
+        self.out.write_char('-')
+    }
+
+    fn push_sextet(&mut self, value: u32) -> fmt::Result {
+        if self.len == self.chunk.len() {
+            self.flush()?;
+        }
+        self.chunk[self.len] = MODIFIED_BASE64[value as usize];
+        self.len += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> fmt::Result {
+        let chunk = core::str::from_utf8(&self.chunk[..self.len]).map_err(|_| fmt::Error)?;
+        self.out.write_str(chunk)?;
+        self.len = 0;
+        Ok(())
+    }
+}
@@ -0,0 +1,297 @@
+use alloc::string::{String, ToString};
+use core::fmt;
+use core::ops::Range;
+
+/// The kind of problem found while decoding a UTF-7 IMAP mailbox name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeErrorKind {
+    /// A character outside the modified BASE64 alphabet appeared inside a shift sequence.
+    InvalidBase64Char(char),
+    /// The bits left over after the last complete byte of a shift sequence were not zero.
+    BadPadding,
+    /// A shift sequence decoded to a number of bytes that is not a whole number of UTF-16 code units.
+    OddByteCount,
+    /// A UTF-16 surrogate, the code unit given, was not part of a valid surrogate pair.
+    UnpairedSurrogate(u16),
+    /// A `&` started a shift sequence that was never closed with `-`.
+    UnterminatedShift,
+    /// Two shift sequences followed each other directly instead of being merged.
+    AdjacentShifts,
+    /// A printable US-ASCII character was encoded inside a shift sequence.
+    ShiftedAscii(char),
+    /// A character outside the printable US-ASCII range appeared without being encoded.
+    UnshiftedChar(char),
+    /// A byte outside the printable US-ASCII range appeared in raw input.
+    UnshiftedByte(u8),
+}
+
+impl fmt::Display for DecodeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeErrorKind::InvalidBase64Char(c) => {
+                write!(f, "invalid modified BASE64 character {:?}", c)
+            }
+            DecodeErrorKind::BadPadding => f.write_str("non-zero padding bits in shift sequence"),
+            DecodeErrorKind::OddByteCount => {
+                f.write_str("shift sequence does not decode to whole UTF-16 code units")
+            }
+            DecodeErrorKind::UnpairedSurrogate(unit @ 0xd800..=0xdbff) => {
+                write!(
+                    f,
+                    "high surrogate 0x{:04X} is not followed by a low surrogate",
+                    unit
+                )
+            }
+            DecodeErrorKind::UnpairedSurrogate(unit) => {
+                write!(
+                    f,
+                    "low surrogate 0x{:04X} is not preceded by a high surrogate",
+                    unit
+                )
+            }
+            DecodeErrorKind::UnterminatedShift => {
+                f.write_str("shift sequence is not terminated by '-'")
+            }
+            DecodeErrorKind::AdjacentShifts => f.write_str("adjacent shift sequences"),
+            DecodeErrorKind::ShiftedAscii(c) => {
+                write!(f, "printable US-ASCII character {:?} in shift sequence", c)
+            }
+            DecodeErrorKind::UnshiftedChar(c) => {
+                write!(f, "character {:?} must be encoded in a shift sequence", c)
+            }
+            DecodeErrorKind::UnshiftedByte(b) => {
+                write!(f, "byte 0x{:02x} is not printable US-ASCII", b)
+            }
+        }
+    }
+}
+
+/// Error returned when a UTF-7 IMAP mailbox name cannot be decoded
+///
+/// Besides the [kind](DecodeError::kind) of problem, the error records where in the
+/// encoded name it was found. [`DecodeError::snippet`] renders that location.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeError {
+    kind: DecodeErrorKind,
+    input: String,
+    sequence: Range<usize>,
+    position: usize,
+}
+
+impl DecodeError {
+    pub(crate) fn new(
+        kind: DecodeErrorKind,
+        input: &str,
+        sequence: Range<usize>,
+        position: usize,
+    ) -> Self {
+        DecodeError {
+            kind,
+            input: input.to_string(),
+            sequence,
+            position,
+        }
+    }
+
+    /// Error for the byte at `position` of raw input, where the bytes before `position`
+    /// are UTF-8.
+    ///
+    /// The input is kept with invalid UTF-8 replaced by U+FFFD, which does not move
+    /// `position`, and the error covers the character that starts there.
+    pub(crate) fn unshifted_byte(bytes: &[u8], position: usize) -> Self {
+        let input = String::from_utf8_lossy(bytes);
+        let len = input[position..].chars().next().map_or(1, char::len_utf8);
+        DecodeError::new(
+            DecodeErrorKind::UnshiftedByte(bytes[position]),
+            &input,
+            position..position + len,
+            position,
+        )
+    }
+
+    /// The kind of problem.
+    pub fn kind(&self) -> DecodeErrorKind {
+        self.kind
+    }
+
+    /// The encoded name that failed to decode.
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    /// Byte range of the offending shift sequence, or of the offending character when it
+    /// is outside of any shift sequence.
+    pub fn sequence(&self) -> Range<usize> {
+        self.sequence.clone()
+    }
+
+    /// Byte offset of the offending character.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Render the error with the input and a marker under the offending character.
+    ///
+    /// ```
+    /// use utf7_imap::try_decode_utf7_imap;
+    ///
+    /// let err = try_decode_utf7_imap("Drafts&BB4E!gQ-").unwrap_err();
+    /// assert_eq!(
+    ///     err.snippet().to_string(),
+    ///     "invalid modified BASE64 character '!' at byte 11\n  \
+    ///      Drafts&BB4E!gQ-\n        \
+    ///      ~~~~~^~~~"
+    /// );
+    /// ```
+    pub fn snippet(&self) -> Snippet<'_> {
+        Snippet(self)
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte {}", self.kind, self.position)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+/// Error returned when the output does not fit in the buffer given to
+/// [`encode_into_slice`](crate::encode_into_slice)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferTooSmall;
+
+impl fmt::Display for BufferTooSmall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("output buffer is too small")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BufferTooSmall {}
+
+/// Error returned by [`decode_into_slice`](crate::decode_into_slice)
+///
+/// Unlike [`DecodeError`] it does not keep a copy of the input, so creating it never
+/// allocates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeSliceError {
+    /// The decoded name does not fit in the output buffer.
+    BufferTooSmall,
+    /// The input is malformed.
+    Invalid {
+        /// The kind of problem.
+        kind: DecodeErrorKind,
+        /// Byte offset of the offending character.
+        position: usize,
+    },
+}
+
+impl From<BufferTooSmall> for DecodeSliceError {
+    fn from(_: BufferTooSmall) -> Self {
+        DecodeSliceError::BufferTooSmall
+    }
+}
+
+impl fmt::Display for DecodeSliceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeSliceError::BufferTooSmall => BufferTooSmall.fmt(f),
+            DecodeSliceError::Invalid { kind, position } => {
+                write!(f, "{} at byte {}", kind, position)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeSliceError {}
+
+/// Error returned when building or parsing a [`MailboxPath`](crate::MailboxPath)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathError {
+    /// The delimiter is not printable US-ASCII, or is `&`.
+    InvalidDelimiter(char),
+    /// A segment contains the delimiter.
+    DelimiterInSegment {
+        /// Index of the segment.
+        index: usize,
+        /// The delimiter.
+        delimiter: char,
+    },
+    /// The encoded name cannot be decoded.
+    Decode(DecodeError),
+}
+
+impl From<DecodeError> for PathError {
+    fn from(err: DecodeError) -> Self {
+        PathError::Decode(err)
+    }
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathError::InvalidDelimiter(c) => {
+                write!(f, "{:?} cannot be used as a hierarchy delimiter", c)
+            }
+            PathError::DelimiterInSegment { index, delimiter } => {
+                write!(
+                    f,
+                    "segment {} contains the delimiter {:?}",
+                    index, delimiter
+                )
+            }
+            PathError::Decode(err) => err.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PathError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PathError::Decode(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Compiler-style rendering of a [`DecodeError`], returned by [`DecodeError::snippet`]
+#[derive(Debug, Clone, Copy)]
+pub struct Snippet<'a>(&'a DecodeError);
+
+impl fmt::Display for Snippet<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let err = self.0;
+        let columns = |end: usize| err.input[..end].chars().count();
+        let start = columns(err.sequence.start);
+        let position = columns(err.position);
+        let end = columns(err.sequence.end).max(position + 1);
+        writeln!(f, "{}", err)?;
+        writeln!(f, "  {}", err.input)?;
+        write!(f, "  {:start$}", "")?;
+        for column in start..end {
+            f.write_str(if column == position { "^" } else { "~" })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "miette")]
+impl miette::Diagnostic for DecodeError {
+    fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+        Some(&self.input)
+    }
+
+    fn labels(&self) -> Option<alloc::boxed::Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        let len = self.input[self.position..]
+            .chars()
+            .next()
+            .map_or(0, char::len_utf8);
+        Some(alloc::boxed::Box::new(core::iter::once(
+            miette::LabeledSpan::at(self.position..self.position + len, self.kind.to_string()),
+        )))
+    }
+}
@@ -1,13 +1,54 @@
 //! A Rust library for encoding and decoding [UTF-7](https://datatracker.ietf.org/doc/html/rfc2152) string as defined by the [IMAP](https://datatracker.ietf.org/doc/html/rfc3501) standard in [RFC 3501 (#5.1.3)](https://datatracker.ietf.org/doc/html/rfc3501#section-5.1.3).
 //!
 //! Idea is based on Python [mutf7](https://github.com/cheshire-mouse/mutf7) library.
+//!
+//! # Features
+//!
+//! - `std` (default): implements `std::error::Error` for [`DecodeError`] and provides
+//!   `encode_to_io`. Without it the crate is `no_std` and only needs `alloc`.
+//! - `miette`: implements `miette::Diagnostic` for [`DecodeError`].
+//! - `serde`: adds the `serde` module and implements `Serialize` and `Deserialize` for
+//!   the mailbox name types and [`MailboxPath`].
+
+// Tests always link `std`, for its prelude and collections.
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
+
+extern crate alloc;
 
-extern crate base64;
-extern crate encoding_rs;
-extern crate regex;
+mod canonical;
+mod classify;
+mod codec;
+mod command;
+mod decode;
+mod encode;
+mod error;
+pub mod list;
+mod mailbox;
+mod path;
+#[cfg(feature = "serde")]
+pub mod serde;
+mod stream;
+mod wire;
 
-use encoding_rs::UTF_16BE;
-use regex::{Captures, Regex};
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::io;
+
+pub use canonical::{canonical_eq, canonicalize_utf7_imap, CanonicalName};
+pub use classify::{classify, Classification, NameEncoding};
+pub use codec::MailboxCodec;
+pub use command::{astring_argument, mailbox_argument, LiteralSupport};
+pub use error::{
+    BufferTooSmall, DecodeError, DecodeErrorKind, DecodeSliceError, PathError, Snippet,
+};
+pub use mailbox::MailboxName;
+pub use path::MailboxPath;
+pub use stream::{Utf7ImapDecoder, Utf7ImapEncoder};
+pub use wire::{Utf7ImapStr, Utf7ImapString};
 
 /// Encode UTF-7 IMAP mailbox name
 ///
@@ -22,81 +63,302 @@ use regex::{Captures, Regex};
 /// assert_eq!(utf7_imap::encode_utf7_imap(test_string), "&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-");
 /// ```
 pub fn encode_utf7_imap(text: String) -> String {
-    let mut result = "".to_string();
-    let text = text.replace('&', "&-");
-    let mut text = text.as_str();
-    while !text.is_empty() {
-        result = format!("{}{}", result, get_ascii(text));
-        text = remove_ascii(text);
-        if !text.is_empty() {
-            let tmp = get_nonascii(text);
-            result = format!("{}{}", result, encode_modified_utf7(tmp.to_string()));
-            text = remove_nonascii(text);
-        }
+    if !needs_encoding(&text) {
+        return text;
     }
-    result
+    encode(&text).into_owned()
 }
-fn is_ascii_custom(c: u8) -> bool {
-    (0x20..=0x7f).contains(&c)
+
+/// Encode UTF-7 IMAP mailbox name, borrowing the input if it needs no encoding
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3501#section-5.1.3>
+///
+/// # Usage:
+///
+/// ```
+/// use std::borrow::Cow;
+/// use utf7_imap::encode;
+///
+/// assert!(matches!(encode("Archive/2024"), Cow::Borrowed("Archive/2024")));
+/// assert_eq!(encode("Отправленные"), "&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-");
+/// ```
+pub fn encode(text: &str) -> Cow<'_, str> {
+    if !needs_encoding(text) {
+        return Cow::Borrowed(text);
+    }
+    let mut result = String::with_capacity(encoded_len(text));
+    // Writing to a `String` cannot fail.
+    let _ = encode_to_fmt(text, &mut result);
+    Cow::Owned(result)
 }
 
-fn get_ascii(s: &str) -> &str {
-    let bytes = s.as_bytes();
-    for (i, &item) in bytes.iter().enumerate() {
-        if !is_ascii_custom(item) {
-            return &s[0..i];
-        }
+/// Encode UTF-7 IMAP mailbox name as raw IMAP wire data
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3501#section-5.1.3>
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::encode_to_bytes;
+///
+/// assert_eq!(encode_to_bytes("Отправленные"), b"&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-");
+/// ```
+pub fn encode_to_bytes(text: &str) -> Vec<u8> {
+    encode(text).into_owned().into_bytes()
+}
+
+/// Encode UTF-7 IMAP mailbox name into a [`fmt::Write`]
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3501#section-5.1.3>
+///
+/// Writes the same output as [`encode_utf7_imap`] without allocating.
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::encode_to_fmt;
+///
+/// let mut command = String::from("SELECT ");
+/// encode_to_fmt("Отправленные", &mut command).unwrap();
+/// assert_eq!(command, "SELECT &BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-");
+/// ```
+pub fn encode_to_fmt<W: fmt::Write + ?Sized>(text: &str, out: &mut W) -> fmt::Result {
+    encode::encode(text, out)
+}
+
+/// Encode UTF-7 IMAP mailbox name into an [`io::Write`]
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3501#section-5.1.3>
+///
+/// Writes the same output as [`encode_utf7_imap`] without allocating.
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::encode_to_io;
+///
+/// let mut command = b"SELECT ".to_vec();
+/// encode_to_io("Отправленные", &mut command).unwrap();
+/// assert_eq!(command, b"SELECT &BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-");
+/// ```
+#[cfg(feature = "std")]
+pub fn encode_to_io<W: io::Write + ?Sized>(text: &str, out: &mut W) -> io::Result<()> {
+    let mut adapter = IoAdapter {
+        inner: out,
+        error: Ok(()),
+    };
+    match encode_to_fmt(text, &mut adapter) {
+        Ok(()) => Ok(()),
+        Err(fmt::Error) => adapter.error,
     }
-    s
 }
 
-fn get_nonascii(s: &str) -> &str {
-    let bytes = s.as_bytes();
-    for (i, &item) in bytes.iter().enumerate() {
-        if is_ascii_custom(item) {
-            return &s[0..i];
-        }
+/// Passes text written with [`fmt::Write`] on to an [`io::Write`], keeping the I/O error.
+#[cfg(feature = "std")]
+struct IoAdapter<'a, W: ?Sized> {
+    inner: &'a mut W,
+    error: io::Result<()>,
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write + ?Sized> fmt::Write for IoAdapter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|err| {
+            self.error = Err(err);
+            fmt::Error
+        })
     }
-    s
 }
 
-fn remove_ascii(s: &str) -> &str {
-    let bytes = s.as_bytes();
-    for (i, &item) in bytes.iter().enumerate() {
-        if !is_ascii_custom(item) {
-            return &s[i..];
-        }
+/// Encode UTF-7 IMAP mailbox name into a byte slice
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3501#section-5.1.3>
+///
+/// Writes the same output as [`encode_utf7_imap`] and returns its length. This never
+/// allocates. A buffer of [`encoded_len`], or [`max_encoded_len`] of the input length,
+/// is large enough.
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::{encode_into_slice, BufferTooSmall};
+///
+/// let mut buffer = [0; 64];
+/// let len = encode_into_slice("Отправленные", &mut buffer).unwrap();
+/// assert_eq!(&buffer[..len], b"&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-");
+/// assert_eq!(encode_into_slice("Отправленные", &mut buffer[..8]), Err(BufferTooSmall));
+/// ```
+pub fn encode_into_slice(text: &str, dst: &mut [u8]) -> Result<usize, BufferTooSmall> {
+    let mut out = SliceWriter {
+        buffer: dst,
+        len: 0,
+    };
+    encode_to_fmt(text, &mut out).map_err(|_| BufferTooSmall)?;
+    Ok(out.len)
+}
+
+/// Writes into a byte slice, failing once it is full.
+pub(crate) struct SliceWriter<'a> {
+    pub(crate) buffer: &'a mut [u8],
+    pub(crate) len: usize,
+}
+
+impl fmt::Write for SliceWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        self.buffer
+            .get_mut(self.len..end)
+            .ok_or(fmt::Error)?
+            .copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
     }
-    ""
 }
 
-fn remove_nonascii(s: &str) -> &str {
-    let bytes = s.as_bytes();
-    for (i, &item) in bytes.iter().enumerate() {
-        if is_ascii_custom(item) {
-            return &s[i..];
+/// Upper bound of the length of the UTF-7 IMAP encoding of a mailbox name of
+/// `input_len` bytes of UTF-8
+///
+/// The worst case alternates `&` with control characters, which take one byte each
+/// and are encoded as `&-` and as a shift sequence of five bytes. The bound saturates at
+/// `usize::MAX` instead of overflowing.
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::{encoded_len, max_encoded_len};
+///
+/// assert_eq!(max_encoded_len(3), 12);
+/// assert_eq!(encoded_len("\u{1}&\u{1}"), 12);
+/// assert_eq!(max_encoded_len(usize::MAX / 2), usize::MAX);
+/// ```
+pub fn max_encoded_len(input_len: usize) -> usize {
+    // (7 * input_len + 3) / 2, written so that it cannot overflow
+    input_len
+        .saturating_mul(3)
+        .saturating_add(input_len.div_ceil(2) + 1)
+}
+
+/// Upper bound of the length of the UTF-8 decoding of a UTF-7 IMAP mailbox name of
+/// `input_len` bytes
+///
+/// Decoded names can be longer than encoded ones: the worst case is a single shift
+/// sequence of characters from U+0800 to U+FFFF, which take three bytes of UTF-8 for
+/// every 8/3 bytes of modified BASE64.
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::{decode_into_slice, max_decoded_len};
+///
+/// let encoded = b"&TgBOjE4JVttOlFFtTgNRa05d-";
+/// let mut buffer = vec![0; max_decoded_len(encoded.len())];
+/// let len = decode_into_slice(encoded, &mut buffer).unwrap();
+/// assert_eq!(std::str::from_utf8(&buffer[..len]).unwrap(), "一二三四五六七八九");
+/// assert!(len > encoded.len());
+/// ```
+pub fn max_decoded_len(input_len: usize) -> usize {
+    input_len.saturating_add(input_len / 8)
+}
+
+/// Length of the UTF-7 IMAP encoding of a mailbox name
+///
+/// This is exactly the length of the output of [`encode_utf7_imap`], so it can be used
+/// to size a buffer before encoding.
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::{encode_to_fmt, encoded_len};
+///
+/// assert_eq!(encoded_len("Отправленные"), "&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-".len());
+///
+/// let mut command = String::with_capacity(7 + encoded_len("Отправленные"));
+/// command.push_str("SELECT ");
+/// encode_to_fmt("Отправленные", &mut command).unwrap();
+/// assert_eq!(command.len(), command.capacity());
+/// ```
+pub fn encoded_len(text: &str) -> usize {
+    encode::encoded_len(text)
+}
+
+/// A name of printable US-ASCII without `&` is its own encoding.
+fn needs_encoding(text: &str) -> bool {
+    encode::literal_len(text.as_bytes()) < text.len()
+}
+
+/// Encode UTF-16 code units as UTF-7 IMAP mailbox name
+///
+/// Unlike [`encode_utf7_imap`], this accepts unpaired surrogates, so a name decoded with
+/// [`SurrogatePolicy::Lossless`] is encoded back to the form the server sent.
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::encode_utf7_imap_utf16;
+///
+/// assert_eq!(encode_utf7_imap_utf16(&[0x41, 0xd83d, 0x42]), "A&2D0-B");
+/// ```
+pub fn encode_utf7_imap_utf16(text: &[u16]) -> String {
+    let is_ascii = |&unit: &u16| u8::try_from(unit).is_ok_and(is_ascii_custom);
+    let mut result = String::with_capacity(text.len());
+    let mut text = text;
+    while !text.is_empty() {
+        let split = text
+            .iter()
+            .position(|unit| !is_ascii(unit))
+            .unwrap_or(text.len());
+        for &unit in &text[..split] {
+            result.push(unit as u8 as char);
+            if unit == u16::from(b'&') {
+                result.push('-');
+            }
         }
+        text = &text[split..];
+        let split = text.iter().position(is_ascii).unwrap_or(text.len());
+        if split > 0 {
+            // Writing to a `String` cannot fail.
+            let _ = encode::write_shift(text[..split].iter().copied(), &mut result);
+        }
+        text = &text[split..];
     }
-    ""
+    result
 }
 
-fn encode_modified_utf7(text: String) -> String {
-    let capacity = 2 * text.len();
-    let mut input = Vec::with_capacity(capacity);
-    let text_u16 = text.encode_utf16();
-    for value in text_u16 {
-        input.extend_from_slice(&value.to_be_bytes());
-    }
-    let text_u16 = base64::encode(input);
-    let text_u16 = text_u16.trim_end_matches('=');
-    let result = text_u16.replace('/', ",");
-    format!("&{}-", result)
+/// Printable US-ASCII, the characters that represent themselves in RFC 3501 §5.1.3.
+pub(crate) fn is_ascii_custom(c: u8) -> bool {
+    (0x20..=0x7e).contains(&c)
 }
 
+pub(crate) fn is_ascii_custom_char(c: char) -> bool {
+    u8::try_from(c).is_ok_and(is_ascii_custom)
+}
+
+/// The modified BASE64 alphabet of RFC 3501 §5.1.3, which uses `,` instead of `/`.
+pub(crate) const MODIFIED_BASE64: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+,";
+
+/// Marks the bytes outside [`MODIFIED_BASE64`] in [`MODIFIED_BASE64_VALUES`].
+pub(crate) const INVALID_BASE64: u8 = 0xff;
+
+/// The value of every byte in [`MODIFIED_BASE64`], indexed by the byte.
+pub(crate) static MODIFIED_BASE64_VALUES: [u8; 256] = {
+    let mut values = [INVALID_BASE64; 256];
+    let mut i = 0;
+    while i < MODIFIED_BASE64.len() {
+        values[MODIFIED_BASE64[i] as usize] = i as u8;
+        i += 1;
+    }
+    values
+};
+
 /// Decode UTF-7 IMAP mailbox name
 ///
 /// <https://datatracker.ietf.org/doc/html/rfc3501#section-5.1.3>
 ///
+/// Unpaired UTF-16 surrogates are replaced with U+FFFD REPLACEMENT CHARACTER, and other
+/// shift sequences that cannot be decoded are left in the result as they are.
+/// Use [`try_decode_utf7_imap`] to find out whether the name was well-formed.
+///
 /// # Usage:
 ///
 /// ```
@@ -106,35 +368,364 @@ fn encode_modified_utf7(text: String) -> String {
 /// assert_eq!(decode_utf7_imap(test_string), "Отправленные");
 /// ```
 pub fn decode_utf7_imap(text: String) -> String {
-    let pattern = Regex::new(r"&([^-]*)-").unwrap();
-    pattern.replace_all(&text, expand).to_string()
+    if !text.contains('&') {
+        return text;
+    }
+    decode::decode_passthrough(&text)
+}
+
+/// Decode UTF-7 IMAP mailbox name, reporting malformed input as an error
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3501#section-5.1.3>
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::{try_decode_utf7_imap, DecodeErrorKind};
+///
+/// assert_eq!(try_decode_utf7_imap("&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-").unwrap(), "Отправленные");
+///
+/// let err = try_decode_utf7_imap("&&-").unwrap_err();
+/// assert_eq!(err.kind(), DecodeErrorKind::InvalidBase64Char('&'));
+/// assert_eq!(err.position(), 1);
+/// ```
+pub fn try_decode_utf7_imap(text: &str) -> Result<String, DecodeError> {
+    decode(text).map(Cow::into_owned)
 }
 
-fn expand(cap: &Captures) -> String {
-    if cap.get(1).unwrap().as_str() == "" {
-        "&".to_string()
-    } else {
-        decode_utf7_part(cap.get(0).unwrap().as_str().to_string())
+/// Decode UTF-7 IMAP mailbox name, borrowing the input if it contains no shift sequence
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3501#section-5.1.3>
+///
+/// This accepts the same names as [`try_decode_utf7_imap`].
+///
+/// # Usage:
+///
+/// ```
+/// use std::borrow::Cow;
+/// use utf7_imap::decode;
+///
+/// assert!(matches!(decode("INBOX"), Ok(Cow::Borrowed("INBOX"))));
+/// assert_eq!(decode("&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-").unwrap(), "Отправленные");
+/// ```
+pub fn decode(text: &str) -> Result<Cow<'_, str>, DecodeError> {
+    if !text.contains('&') {
+        return Ok(Cow::Borrowed(text));
     }
+    decode_utf7_imap_with(text, &DecodeOptions::new()).map(|report| Cow::Owned(report.text))
+}
+
+/// Decode UTF-7 IMAP mailbox name from raw IMAP wire data
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3501#section-5.1.3>
+///
+/// Any byte outside printable US-ASCII is an error. Otherwise this accepts the same names
+/// as [`try_decode_utf7_imap`].
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::{decode_bytes, DecodeErrorKind};
+///
+/// assert_eq!(decode_bytes(b"&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-").unwrap(), "Отправленные");
+/// let err = decode_bytes(b"caf\xc3\xa9").unwrap_err();
+/// assert_eq!(err.kind(), DecodeErrorKind::UnshiftedByte(0xc3));
+/// ```
+pub fn decode_bytes(bytes: &[u8]) -> Result<String, DecodeError> {
+    decode_bytes_with(bytes, &DecodeOptions::new()).map(|report| report.text)
+}
+
+/// Decode UTF-7 IMAP mailbox name from raw IMAP wire data into a byte slice
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3501#section-5.1.3>
+///
+/// Writes the UTF-8 that [`decode_bytes`] returns and reports the same errors, but never
+/// allocates. Returns the number of bytes written. A buffer of [`max_decoded_len`] of the
+/// input length is large enough. A malformed input is reported as
+/// [`DecodeSliceError::Invalid`] even if the buffer is also too small.
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::{decode_into_slice, DecodeErrorKind, DecodeSliceError};
+///
+/// let mut buffer = [0; 64];
+/// let len = decode_into_slice(b"&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-", &mut buffer).unwrap();
+/// assert_eq!(std::str::from_utf8(&buffer[..len]).unwrap(), "Отправленные");
+///
+/// let err = decode_into_slice(b"&&-", &mut buffer).unwrap_err();
+/// assert_eq!(
+///     err,
+///     DecodeSliceError::Invalid {
+///         kind: DecodeErrorKind::InvalidBase64Char('&'),
+///         position: 1,
+///     }
+/// );
+/// ```
+pub fn decode_into_slice(bytes: &[u8], dst: &mut [u8]) -> Result<usize, DecodeSliceError> {
+    decode::decode_into_slice(bytes, dst)
+}
+
+/// Decode UTF-7 IMAP mailbox name from raw IMAP wire data using the given options
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3501#section-5.1.3>
+///
+/// Bytes outside printable US-ASCII are an error, except that [`Mode::Lenient`] accepts
+/// raw UTF-8 and reports it as [`RepairKind::RawNonAscii`].
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::{decode_bytes_with, DecodeOptions, Mode, RepairKind};
+///
+/// let options = DecodeOptions::new().mode(Mode::Lenient);
+/// let report = decode_bytes_with(b"caf\xc3\xa9", &options).unwrap();
+/// assert_eq!(report.text, "café");
+/// assert_eq!(report.repairs[0].kind, RepairKind::RawNonAscii);
+/// ```
+pub fn decode_bytes_with(
+    bytes: &[u8],
+    options: &DecodeOptions,
+) -> Result<DecodeReport, DecodeError> {
+    let raw_utf8 = options.mode == Mode::Lenient;
+    let accepted = |byte: u8| is_ascii_custom(byte) || (raw_utf8 && !byte.is_ascii());
+    let rejected = bytes
+        .iter()
+        .position(|&byte| !accepted(byte))
+        .unwrap_or(bytes.len());
+    // Report the first problem, so that everything before it is UTF-8.
+    let text = match core::str::from_utf8(&bytes[..rejected]) {
+        Ok(text) if rejected == bytes.len() => text,
+        Ok(_) => return Err(DecodeError::unshifted_byte(bytes, rejected)),
+        Err(err) => return Err(DecodeError::unshifted_byte(bytes, err.valid_up_to())),
+    };
+    decode_utf7_imap_with(text, options)
+}
+
+/// Decode UTF-7 IMAP mailbox name, rejecting anything but the canonical encoding
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3501#section-5.1.3>
+///
+/// On top of the checks done by [`try_decode_utf7_imap`], this rejects adjacent shift
+/// sequences, printable US-ASCII encoded in modified BASE64 and characters outside
+/// the printable US-ASCII range that were not encoded.
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::{decode_utf7_imap_strict, DecodeErrorKind};
+///
+/// assert_eq!(decode_utf7_imap_strict("th&AOkA4g-tre").unwrap(), "théâtre");
+/// let err = decode_utf7_imap_strict("th&AOk-&AOI-tre").unwrap_err();
+/// assert_eq!(err.kind(), DecodeErrorKind::AdjacentShifts);
+/// ```
+pub fn decode_utf7_imap_strict(text: &str) -> Result<String, DecodeError> {
+    decode_utf7_imap_with(text, &DecodeOptions::new().mode(Mode::Strict)).map(|report| report.text)
+}
+
+/// Check that a UTF-7 IMAP mailbox name is in the canonical form required by RFC 3501
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3501#section-5.1.3>
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::is_valid_utf7_imap;
+///
+/// assert!(is_valid_utf7_imap("&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-"));
+/// assert!(!is_valid_utf7_imap("Tom & Jerry"));
+/// ```
+pub fn is_valid_utf7_imap(text: &str) -> bool {
+    decode_utf7_imap_strict(text).is_ok()
+}
+
+/// What to do with a shift sequence that runs to the end of the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Unterminated {
+    /// Fail with [`DecodeErrorKind::UnterminatedShift`].
+    #[default]
+    Error,
+    /// Decode the complete UTF-16 code units and set [`DecodeReport::truncated`].
+    Truncate,
+}
+
+/// What to do with a UTF-16 surrogate that is not part of a valid pair
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SurrogatePolicy {
+    /// Fail with [`DecodeErrorKind::UnpairedSurrogate`].
+    #[default]
+    Error,
+    /// Replace the surrogate with U+FFFD REPLACEMENT CHARACTER.
+    Replace,
+    /// Replace the surrogate with U+FFFD in [`DecodeReport::text`], and keep the raw
+    /// code units in [`DecodeReport::utf16`].
+    Lossless,
+}
+
+/// How closely the input has to follow RFC 3501
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// Decode any well-formed shift sequence, as [`try_decode_utf7_imap`] does.
+    #[default]
+    Standard,
+    /// Reject non-canonical encodings, as [`decode_utf7_imap_strict`] does.
+    Strict,
+    /// Accept the variants of modified UTF-7 produced by known server bugs and list
+    /// each one in [`DecodeReport::repairs`].
+    Lenient,
+}
+
+/// Settings for [`decode_utf7_imap_with`]
+#[derive(Debug, Clone, Default)]
+pub struct DecodeOptions {
+    mode: Mode,
+    unterminated: Unterminated,
+    surrogates: SurrogatePolicy,
 }
 
-fn decode_utf7_part(text: String) -> String {
-    if text == "&-" {
-        return String::from("&");
+impl DecodeOptions {
+    /// Options used by [`try_decode_utf7_imap`].
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    let text_mb64 = &text[1..text.len() - 1];
-    let mut text_b64 = text_mb64.replace(',', "/");
+    /// Choose how closely the input has to follow RFC 3501.
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
 
-    while (text_b64.len() % 4) != 0 {
-        text_b64 += "=";
+    /// Choose how a shift sequence without a closing `-` is handled.
+    pub fn unterminated(mut self, policy: Unterminated) -> Self {
+        self.unterminated = policy;
+        self
     }
 
-    let text_u16 = base64::decode(text_b64).unwrap();
-    let (cow, _encoding_used, _had_errors) = UTF_16BE.decode(&text_u16);
-    let result = cow.as_ref();
+    /// Choose how unpaired UTF-16 surrogates are handled.
+    pub fn surrogates(mut self, policy: SurrogatePolicy) -> Self {
+        self.surrogates = policy;
+        self
+    }
+}
+
+/// A deviation from RFC 3501 accepted by [`Mode::Lenient`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepairKind {
+    /// A shift sequence used `/` from standard BASE64 instead of `,`.
+    SlashInBase64,
+    /// A shift sequence ended with `=` padding characters, which were dropped.
+    PaddingCharacters,
+    /// The bits left over after the last complete byte of a shift sequence were not zero.
+    NonZeroPadding,
+    /// A shift sequence ended in the middle of a UTF-16 code unit, which was dropped.
+    IncompleteCodeUnit,
+    /// A shift sequence was followed by other text without a closing `-`.
+    MissingTerminator,
+    /// A `&` that does not start a shift sequence was not escaped as `&-`.
+    UnescapedAmpersand,
+    /// A character outside US-ASCII was sent as raw UTF-8 instead of being encoded.
+    RawNonAscii,
+}
+
+impl fmt::Display for RepairKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RepairKind::SlashInBase64 => "'/' instead of ',' in shift sequence",
+            RepairKind::PaddingCharacters => "'=' padding in shift sequence",
+            RepairKind::NonZeroPadding => "non-zero padding bits in shift sequence",
+            RepairKind::IncompleteCodeUnit => "incomplete UTF-16 code unit in shift sequence",
+            RepairKind::MissingTerminator => "shift sequence is not terminated by '-'",
+            RepairKind::UnescapedAmpersand => "'&' is not escaped as '&-'",
+            RepairKind::RawNonAscii => "raw non-ASCII character",
+        })
+    }
+}
+
+/// A deviation from RFC 3501 and where in the encoded name it was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repair {
+    /// What was repaired.
+    pub kind: RepairKind,
+    /// Byte range of the shift sequence or character that was repaired.
+    pub span: Range<usize>,
+}
 
-    String::from(result)
+/// Result of [`decode_utf7_imap_with`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeReport {
+    /// The decoded mailbox name.
+    pub text: String,
+    /// The name ended inside a shift sequence, so trailing characters may be missing.
+    pub truncated: bool,
+    /// The decoded name as UTF-16 code units, including unpaired surrogates.
+    ///
+    /// Only set with [`SurrogatePolicy::Lossless`].
+    pub utf16: Option<Vec<u16>>,
+    /// Deviations from RFC 3501 that were accepted, in input order.
+    ///
+    /// Always empty unless decoding with [`Mode::Lenient`].
+    pub repairs: Vec<Repair>,
+}
+
+impl DecodeReport {
+    /// Encode the decoded name again.
+    ///
+    /// With [`SurrogatePolicy::Lossless`] this reproduces a canonically encoded input
+    /// exactly, even if it contained unpaired surrogates.
+    pub fn encode(&self) -> String {
+        match &self.utf16 {
+            Some(units) => encode_utf7_imap_utf16(units),
+            None => encode_utf7_imap(self.text.clone()),
+        }
+    }
+}
+
+/// Decode UTF-7 IMAP mailbox name, repairing the mistakes of known buggy servers
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3501#section-5.1.3>
+///
+/// This uses [`Mode::Lenient`], keeps what can be decoded of an unterminated shift sequence
+/// and replaces unpaired surrogates, so it never fails.
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::{decode_utf7_imap_lenient, RepairKind};
+///
+/// let report = decode_utf7_imap_lenient("&BB4EQgQ/BEAEMAQyBDsENQQ9BD0ESwQ1-");
+/// assert_eq!(report.text, "Отправленные");
+/// assert_eq!(report.repairs[0].kind, RepairKind::SlashInBase64);
+/// ```
+pub fn decode_utf7_imap_lenient(text: &str) -> DecodeReport {
+    let options = DecodeOptions::new()
+        .mode(Mode::Lenient)
+        .unterminated(Unterminated::Truncate)
+        .surrogates(SurrogatePolicy::Replace);
+    match decode_utf7_imap_with(text, &options) {
+        Ok(report) => report,
+        Err(_) => unreachable!("lenient decoding accepts any input"),
+    }
+}
+
+/// Decode UTF-7 IMAP mailbox name using the given options
+///
+/// <https://datatracker.ietf.org/doc/html/rfc3501#section-5.1.3>
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::{decode_utf7_imap_with, DecodeOptions, Unterminated};
+///
+/// let options = DecodeOptions::new().unterminated(Unterminated::Truncate);
+/// let report = decode_utf7_imap_with("Drafts&BB4EQgQ", &options).unwrap();
+/// assert_eq!(report.text, "DraftsОт");
+/// assert!(report.truncated);
+/// ```
+pub fn decode_utf7_imap_with(
+    text: &str,
+    options: &DecodeOptions,
+) -> Result<DecodeReport, DecodeError> {
+    decode::decode(text, options)
 }
 
 #[cfg(test)]
@@ -160,6 +751,213 @@ mod tests {
         assert_eq!(encode_utf7_imap(test_string), "th&AOkA4g-tre")
     }
 
+    /// Vectors for every class of code point, from the examples in RFC 3501 §5.1.3 and
+    /// the output of the Python mutf7 library.
+    const CONFORMANCE: &[(&str, &str)] = &[
+        // C0 controls
+        ("\u{0}", "&AAA-"),
+        ("\u{1f}", "&AB8-"),
+        ("\t\n", "&AAkACg-"),
+        // DEL
+        ("\u{7f}", "&AH8-"),
+        ("a\u{7f}b", "a&AH8-b"),
+        // printable US-ASCII
+        (" ", " "),
+        ("~", "~"),
+        ("-", "-"),
+        ("&", "&-"),
+        ("&&", "&-&-"),
+        ("Tom & Jerry", "Tom &- Jerry"),
+        // BMP
+        ("\u{80}", "&AIA-"),
+        ("~peter/mail/台北/日本語", "~peter/mail/&U,BTFw-/&ZeVnLIqe-"),
+        ("Отправленные", "&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-"),
+        ("\u{ffff}", "&,,8-"),
+        // around the surrogate range
+        ("\u{d7ff}", "&1,8-"),
+        ("\u{e000}", "&4AA-"),
+        // astral
+        ("\u{10000}", "&2ADcAA-"),
+        ("😀", "&2D3eAA-"),
+        ("\u{10ffff}", "&2,,f,w-"),
+    ];
+
+    #[test]
+    fn conformance_encode() {
+        for &(decoded, encoded) in CONFORMANCE {
+            assert_eq!(
+                encode_utf7_imap(decoded.to_string()),
+                encoded,
+                "{:?}",
+                decoded
+            );
+        }
+    }
+
+    #[test]
+    fn conformance_decode() {
+        for &(decoded, encoded) in CONFORMANCE {
+            assert_eq!(
+                decode_utf7_imap_strict(encoded).unwrap(),
+                decoded,
+                "{:?}",
+                encoded
+            );
+            assert!(is_valid_utf7_imap(encoded), "{:?}", encoded);
+        }
+        assert_eq!(
+            decode_utf7_imap_strict("a\u{7f}b").map_err(|err| err.kind()),
+            Err(DecodeErrorKind::UnshiftedChar('\u{7f}'))
+        );
+    }
+
+    #[test]
+    fn conformance_lone_surrogates() {
+        for (unit, encoded) in [
+            (0xd800, "&2AA-"),
+            (0xdbff, "&2,8-"),
+            (0xdc00, "&3AA-"),
+            (0xdfff, "&3,8-"),
+        ] {
+            assert_eq!(encode_utf7_imap_utf16(&[unit]), encoded);
+            assert_eq!(
+                try_decode_utf7_imap(encoded).map_err(|err| err.kind()),
+                Err(DecodeErrorKind::UnpairedSurrogate(unit))
+            );
+        }
+    }
+
+    #[test]
+    fn borrow_plain_names() {
+        for name in ["INBOX", "Sent", "Archive/2024", ""] {
+            assert!(matches!(encode(name), Cow::Borrowed(_)));
+            assert!(matches!(decode(name), Ok(Cow::Borrowed(_))));
+        }
+        assert!(matches!(encode("Tom & Jerry"), Cow::Owned(_)));
+        assert!(matches!(encode("a\u{7f}"), Cow::Owned(_)));
+        assert_eq!(decode("Tom &- Jerry").unwrap(), "Tom & Jerry");
+        assert!(decode("&&-").is_err());
+    }
+
+    #[test]
+    fn encode_ascii_runs() {
+        // every position of a run longer than the words scanned by the ASCII fast path
+        for position in 0..40 {
+            for special in ["&", "\u{7f}", "\t", "é", "😀"] {
+                let mut name = "x".repeat(40);
+                name.replace_range(position..position + 1, special);
+                let expected = match special {
+                    "&" => "&-",
+                    "\u{7f}" => "&AH8-",
+                    "\t" => "&AAk-",
+                    "é" => "&AOk-",
+                    _ => "&2D3eAA-",
+                };
+                assert_eq!(
+                    encode(&name),
+                    name.replacen(special, expected, 1),
+                    "{:?}",
+                    name
+                );
+                assert_eq!(encoded_len(&name), encode(&name).len());
+            }
+        }
+    }
+
+    #[test]
+    fn encode_decode_slices() {
+        let mut buffer = [0; 8];
+        assert_eq!(
+            encode_into_slice("théâtre", &mut buffer),
+            Err(BufferTooSmall)
+        );
+        assert_eq!(encode_into_slice("&", &mut buffer), Ok(2));
+        assert_eq!(&buffer[..2], b"&-");
+        assert_eq!(
+            decode_into_slice(b"th&AOkA4g-tre", &mut buffer),
+            Err(DecodeSliceError::BufferTooSmall)
+        );
+        assert_eq!(decode_into_slice(b"th&AOk-", &mut buffer), Ok(4));
+        assert_eq!(&buffer[..4], "thé".as_bytes());
+        assert_eq!(
+            decode_into_slice(b"caf\xc3\xa9", &mut buffer),
+            Err(DecodeSliceError::Invalid {
+                kind: DecodeErrorKind::UnshiftedByte(0xc3),
+                position: 3
+            })
+        );
+        // malformed input is reported even when the output does not fit either
+        assert_eq!(
+            decode_into_slice(b"th&AOkA4gDpAOkA6QDp", &mut buffer),
+            Err(DecodeSliceError::Invalid {
+                kind: DecodeErrorKind::UnterminatedShift,
+                position: 2
+            })
+        );
+
+        // the decoded name is longer than the encoded one
+        let encoded = encode("一二三四五六七八九");
+        assert_eq!(encoded.len(), 26);
+        let mut buffer = [0; 26];
+        assert_eq!(
+            decode_into_slice(encoded.as_bytes(), &mut buffer),
+            Err(DecodeSliceError::BufferTooSmall)
+        );
+        let mut buffer = [0; 29];
+        assert_eq!(max_decoded_len(encoded.len()), buffer.len());
+        assert_eq!(decode_into_slice(encoded.as_bytes(), &mut buffer), Ok(27));
+    }
+
+    #[test]
+    fn max_lengths_saturate() {
+        for len in 0..100 {
+            assert_eq!(max_encoded_len(len), (7 * len + 3) / 2);
+        }
+        assert_eq!(max_encoded_len(usize::MAX / 2), usize::MAX);
+        assert_eq!(max_encoded_len(usize::MAX), usize::MAX);
+        assert_eq!(max_decoded_len(usize::MAX), usize::MAX);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn encode_to_writers() {
+        // longer than one chunk of the BASE64 encoder
+        let name = "Отправленные ".repeat(10) + &"😀".repeat(40);
+        let mut fmt_out = String::new();
+        encode_to_fmt(&name, &mut fmt_out).unwrap();
+        let mut io_out = Vec::new();
+        encode_to_io(&name, &mut io_out).unwrap();
+        assert_eq!(fmt_out.as_bytes(), io_out);
+        assert_eq!(decode_utf7_imap_strict(&fmt_out).unwrap(), name);
+
+        let mut full = [0; 8];
+        let err = encode_to_io("Tom & Jerry", &mut &mut full[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+    }
+
+    #[test]
+    fn decode_raw_bytes() {
+        assert_eq!(decode_bytes(b"th&AOkA4g-tre").unwrap(), "théâtre");
+        let err = decode_bytes(b"a\tb").unwrap_err();
+        assert_eq!(err.kind(), DecodeErrorKind::UnshiftedByte(b'\t'));
+        assert_eq!(err.position(), 1);
+        let err = decode_bytes(b"th\xc3\xa9\xe2tre").unwrap_err();
+        assert_eq!(err.position(), 2);
+
+        let lenient = DecodeOptions::new().mode(Mode::Lenient);
+        assert_eq!(
+            decode_bytes_with(b"th\xc3\xa9&AOI-tre", &lenient)
+                .unwrap()
+                .text,
+            "théâtre"
+        );
+        let err = decode_bytes_with(b"th\xc3\xa9\xe2tre", &lenient).unwrap_err();
+        assert_eq!(err.kind(), DecodeErrorKind::UnshiftedByte(0xe2));
+        assert_eq!(err.position(), 4);
+        assert!(decode_bytes_with(b"a\x7fb", &lenient).is_err());
+        assert_eq!(encode_to_bytes("a\x7fb"), b"a&AH8-b");
+    }
+
     #[test]
     fn decode_test() {
         let test_string = String::from("&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-");
@@ -178,6 +976,278 @@ mod tests {
         assert_eq!(decode_utf7_imap(test_string), "théâtre")
     }
 
+    #[test]
+    fn decode_malformed_does_not_panic() {
+        assert_eq!(decode_utf7_imap(String::from("&&-")), "&&-");
+        assert_eq!(decode_utf7_imap(String::from("a&AOk-&Jjo")), "aé&Jjo");
+        assert_eq!(decode_utf7_imap(String::from("&AO!k-&AOk-")), "&AO!k-é");
+    }
+
+    #[test]
+    fn decode_replaces_unpaired_surrogates() {
+        assert_eq!(decode_utf7_imap(String::from("&2D0-")), "\u{fffd}");
+        assert_eq!(
+            decode_utf7_imap(String::from("a&AOnYPQBh-b")),
+            "aé\u{fffd}ab"
+        );
+    }
+
+    #[test]
+    fn modified_base64_table() {
+        for byte in 0..=u8::MAX {
+            let expected = MODIFIED_BASE64.iter().position(|&c| c == byte);
+            let value = MODIFIED_BASE64_VALUES[usize::from(byte)];
+            assert_eq!(
+                (value != INVALID_BASE64).then_some(usize::from(value)),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_char_in_unterminated_shift() {
+        assert_eq!(
+            try_decode_utf7_imap("Tom & Jerry").map_err(|err| err.kind()),
+            Err(DecodeErrorKind::UnterminatedShift)
+        );
+        let options = DecodeOptions::new().unterminated(Unterminated::Truncate);
+        assert_eq!(
+            decode_utf7_imap_with("Tom & Jerry", &options).map_err(|err| err.kind()),
+            Err(DecodeErrorKind::InvalidBase64Char(' '))
+        );
+    }
+
+    #[test]
+    fn try_decode_errors() {
+        assert_eq!(
+            try_decode_utf7_imap("&&-").map_err(|err| err.kind()),
+            Err(DecodeErrorKind::InvalidBase64Char('&'))
+        );
+        assert_eq!(
+            try_decode_utf7_imap("&AOl-").map_err(|err| err.kind()),
+            Err(DecodeErrorKind::BadPadding)
+        );
+        assert_eq!(
+            try_decode_utf7_imap("&AOkA-").map_err(|err| err.kind()),
+            Err(DecodeErrorKind::OddByteCount)
+        );
+        assert_eq!(
+            try_decode_utf7_imap("&2D0-").map_err(|err| err.kind()),
+            Err(DecodeErrorKind::UnpairedSurrogate(0xd83d))
+        );
+        assert_eq!(
+            try_decode_utf7_imap("Drafts&BB4EQgQ").map_err(|err| err.kind()),
+            Err(DecodeErrorKind::UnterminatedShift)
+        );
+        assert_eq!(try_decode_utf7_imap("Tom &- Jerry").unwrap(), "Tom & Jerry");
+    }
+
+    #[test]
+    fn error_positions() {
+        let err = try_decode_utf7_imap("Drafts&BB4E!gQ-").unwrap_err();
+        assert_eq!(err.sequence(), 6..15);
+        assert_eq!(err.position(), 11);
+        assert_eq!(err.input(), "Drafts&BB4E!gQ-");
+        assert_eq!(
+            err.to_string(),
+            "invalid modified BASE64 character '!' at byte 11"
+        );
+
+        // the high surrogate is the second code unit, starting in the third character
+        let err = try_decode_utf7_imap("a&AOnYPQBh-").unwrap_err();
+        assert_eq!(err.kind(), DecodeErrorKind::UnpairedSurrogate(0xd83d));
+        assert_eq!(err.position(), 4);
+        assert_eq!(
+            err.to_string(),
+            "high surrogate 0xD83D is not followed by a low surrogate at byte 4"
+        );
+
+        // a low surrogate after a complete pair
+        let err = try_decode_utf7_imap("&2D3eAN4A-").unwrap_err();
+        assert_eq!(err.kind(), DecodeErrorKind::UnpairedSurrogate(0xde00));
+        assert_eq!(err.position(), 6);
+
+        let err = decode_utf7_imap_strict("Été &AOk-").unwrap_err();
+        assert_eq!((err.sequence(), err.position()), (0..2, 0));
+        assert_eq!(
+            err.snippet().to_string(),
+            "character 'É' must be encoded in a shift sequence at byte 0\n  Été &AOk-\n  ^"
+        );
+
+        let err = decode_utf7_imap_strict("&AOk-&AOI-").unwrap_err();
+        assert_eq!((err.sequence(), err.position()), (5..10, 5));
+    }
+
+    #[test]
+    fn byte_error_snippets() {
+        // the marker covers the whole character
+        let err = decode_bytes(b"caf\xc3\xa9").unwrap_err();
+        assert_eq!((err.sequence(), err.position()), (3..5, 3));
+        assert_eq!(
+            err.snippet().to_string(),
+            "byte 0xc3 is not printable US-ASCII at byte 3\n  café\n     ^"
+        );
+
+        // invalid UTF-8 is shown as U+FFFD
+        let lenient = DecodeOptions::new().mode(Mode::Lenient);
+        let err = decode_bytes_with(b"th\xc3\xa9\xe2tre", &lenient).unwrap_err();
+        assert_eq!(err.kind(), DecodeErrorKind::UnshiftedByte(0xe2));
+        assert_eq!((err.sequence(), err.position()), (4..7, 4));
+        assert_eq!(
+            err.snippet().to_string(),
+            "byte 0xe2 is not printable US-ASCII at byte 4\n  thé\u{fffd}tre\n     ^"
+        );
+
+        // the first problem is reported, whether a control character or invalid UTF-8
+        let err = decode_bytes_with(b"\xff\x01", &lenient).unwrap_err();
+        assert_eq!(err.kind(), DecodeErrorKind::UnshiftedByte(0xff));
+        let err = decode_bytes_with(b"\xc3\x01", &lenient).unwrap_err();
+        assert_eq!(err.kind(), DecodeErrorKind::UnshiftedByte(0xc3));
+        let err = decode_bytes_with(b"\x01\xff", &lenient).unwrap_err();
+        assert_eq!(err.kind(), DecodeErrorKind::UnshiftedByte(0x01));
+        assert_eq!(err.snippet().to_string().lines().count(), 3);
+    }
+
+    #[test]
+    fn strict_rejects_non_canonical() {
+        assert_eq!(
+            decode_utf7_imap_strict("&AOk-&AOI-").map_err(|err| err.kind()),
+            Err(DecodeErrorKind::AdjacentShifts)
+        );
+        assert_eq!(
+            decode_utf7_imap_strict("&AGEAYgBj-").map_err(|err| err.kind()),
+            Err(DecodeErrorKind::ShiftedAscii('a'))
+        );
+        assert_eq!(
+            decode_utf7_imap_strict("&AOl-").map_err(|err| err.kind()),
+            Err(DecodeErrorKind::BadPadding)
+        );
+        assert_eq!(
+            decode_utf7_imap_strict("Tom & Jerry").map_err(|err| err.kind()),
+            Err(DecodeErrorKind::UnterminatedShift)
+        );
+        assert_eq!(
+            decode_utf7_imap_strict("théâtre").map_err(|err| err.kind()),
+            Err(DecodeErrorKind::UnshiftedChar('é'))
+        );
+        assert_eq!(decode_utf7_imap_strict("&-&AOk-").unwrap(), "&é");
+        assert_eq!(decode_utf7_imap_strict("&AOk-&-").unwrap(), "é&");
+        assert_eq!(try_decode_utf7_imap("&AOk-&AOI-").unwrap(), "éâ");
+    }
+
+    #[test]
+    fn unterminated_shift() {
+        let options = DecodeOptions::new().unterminated(Unterminated::Truncate);
+        assert_eq!(
+            decode_utf7_imap_with("Drafts&BB4EQgQ", &DecodeOptions::new())
+                .map_err(|err| err.kind()),
+            Err(DecodeErrorKind::UnterminatedShift)
+        );
+        assert_eq!(
+            decode_utf7_imap_with("Drafts&BB4EQgQ", &options).unwrap(),
+            DecodeReport {
+                text: String::from("DraftsОт"),
+                truncated: true,
+                utf16: None,
+                repairs: Vec::new(),
+            }
+        );
+        // the high half of a surrogate pair is dropped with the rest of the pair
+        assert_eq!(decode_utf7_imap_with("&2D3", &options).unwrap().text, "");
+        assert_eq!(
+            decode_utf7_imap_with("Drafts&", &options).unwrap().text,
+            "Drafts"
+        );
+        assert!(!decode_utf7_imap_with("&AOk-", &options).unwrap().truncated);
+        assert_eq!(
+            decode_utf7_imap(String::from("Drafts&BB4EQgQ")),
+            "Drafts&BB4EQgQ"
+        );
+    }
+
+    #[test]
+    fn unpaired_surrogates() {
+        let name = "Half &2D0-emoji";
+        assert_eq!(
+            try_decode_utf7_imap(name).map_err(|err| err.kind()),
+            Err(DecodeErrorKind::UnpairedSurrogate(0xd83d))
+        );
+        let options = DecodeOptions::new().surrogates(SurrogatePolicy::Replace);
+        let report = decode_utf7_imap_with(name, &options).unwrap();
+        assert_eq!(report.text, "Half \u{fffd}emoji");
+        assert_eq!(report.utf16, None);
+
+        let options = DecodeOptions::new().surrogates(SurrogatePolicy::Lossless);
+        let report = decode_utf7_imap_with(name, &options).unwrap();
+        assert_eq!(report.text, "Half \u{fffd}emoji");
+        assert_eq!(report.encode(), name);
+        let report = decode_utf7_imap_with("&-&3gDYPQ-", &options).unwrap();
+        assert_eq!(report.utf16, Some(vec![0x26, 0xde00, 0xd83d]));
+        assert_eq!(report.encode(), "&-&3gDYPQ-");
+    }
+
+    #[test]
+    fn lenient_repairs() {
+        let kinds = |text| {
+            let report = decode_utf7_imap_lenient(text);
+            let kinds: Vec<_> = report.repairs.iter().map(|r| r.kind).collect();
+            (report.text, kinds)
+        };
+        assert_eq!(kinds("th&AOkA4g-tre"), (String::from("théâtre"), vec![]));
+        assert_eq!(
+            kinds("&BB4EQgQ/BEA-"),
+            (String::from("Отпр"), vec![RepairKind::SlashInBase64])
+        );
+        assert_eq!(
+            kinds("&AOk=-"),
+            (String::from("é"), vec![RepairKind::PaddingCharacters])
+        );
+        assert_eq!(
+            kinds("&AOl-"),
+            (String::from("é"), vec![RepairKind::NonZeroPadding])
+        );
+        assert_eq!(
+            kinds("&AOkA-"),
+            (String::from("é"), vec![RepairKind::IncompleteCodeUnit])
+        );
+        assert_eq!(
+            kinds("th&AOkA4g.tre"),
+            (
+                String::from("théâ.tre"),
+                vec![RepairKind::MissingTerminator]
+            )
+        );
+        assert_eq!(
+            kinds("Tom & Jerry"),
+            (
+                String::from("Tom & Jerry"),
+                vec![RepairKind::UnescapedAmpersand]
+            )
+        );
+        assert_eq!(
+            kinds("théâtre"),
+            (
+                String::from("théâtre"),
+                vec![RepairKind::RawNonAscii, RepairKind::RawNonAscii]
+            )
+        );
+        let report = decode_utf7_imap_lenient("a&AOk=-b");
+        assert_eq!(report.repairs[0].span, 1..7);
+
+        // text after padding is not part of the sequence
+        assert_eq!(
+            kinds("&AOk=AOk-"),
+            (
+                String::from("éAOk-"),
+                vec![RepairKind::MissingTerminator, RepairKind::PaddingCharacters]
+            )
+        );
+        assert_eq!(
+            kinds("&AOk==-"),
+            (String::from("é"), vec![RepairKind::PaddingCharacters])
+        );
+    }
+
     use proptest::prelude::*;
     proptest! {
         #![proptest_config(ProptestConfig::with_cases(10000))]
@@ -185,5 +1255,57 @@ mod tests {
         fn fuzzy_dec_enc_check(s in "\\PC*") {
             assert_eq!(decode_utf7_imap(encode_utf7_imap(s.clone())),s)
         }
+
+        #[test]
+        fn fuzzy_encoded_is_valid(s in "\\PC*") {
+            assert!(is_valid_utf7_imap(&encode_utf7_imap(s)))
+        }
+
+        #[test]
+        fn fuzzy_utf16_matches_encode(s in "\\PC*") {
+            let units: Vec<u16> = s.encode_utf16().collect();
+            assert_eq!(encode_utf7_imap_utf16(&units), encode_utf7_imap(s))
+        }
+
+        #[test]
+        fn fuzzy_encoded_len_is_exact(s in "[a-z &~\t\u{7f}]*\\PC*[a-z &~]*") {
+            assert_eq!(encoded_len(&s), encode(&s).len())
+        }
+
+        #[test]
+        fn fuzzy_encode_into_slice(s in "\\PC*") {
+            let mut buffer = vec![0; max_encoded_len(s.len())];
+            let len = encode_into_slice(&s, &mut buffer).unwrap();
+            assert_eq!(&buffer[..len], encode(&s).as_bytes());
+        }
+
+        #[test]
+        fn fuzzy_decode_into_slice(s in "[&A-Za-z0-9+,/=é-]*") {
+            let mut buffer = vec![0; max_decoded_len(s.len())];
+            let result = decode_into_slice(s.as_bytes(), &mut buffer)
+                .map(|len| String::from_utf8(buffer[..len].to_vec()).unwrap());
+            let expected = decode_bytes(s.as_bytes()).map_err(|err| DecodeSliceError::Invalid {
+                kind: err.kind(),
+                position: err.position(),
+            });
+            assert_eq!(result, expected);
+        }
+
+        #[test]
+        fn fuzzy_lenient_accepts_encoded(s in "\\PC*") {
+            let report = decode_utf7_imap_lenient(&encode_utf7_imap(s.clone()));
+            assert_eq!(report.text, s);
+            assert!(report.repairs.is_empty());
+        }
+
+        #[test]
+        fn fuzzy_lenient_never_panics(s in "[&A-Za-z0-9+,/=é. -]*") {
+            let _ = decode_utf7_imap_lenient(&s);
+        }
+
+        #[test]
+        fn fuzzy_try_decode_never_panics(s in "[&A-Za-z0-9+,/=-]*") {
+            let _ = try_decode_utf7_imap(&s);
+        }
     }
 }
@@ -0,0 +1,533 @@
+//! Parse IMAP `LIST` and `LSUB` responses and decode the mailbox names in them
+//!
+//! <https://datatracker.ietf.org/doc/html/rfc3501#section-7.2.2>
+//!
+//! The mailbox name may be sent as an atom, a quoted string with `\"` and `\\` escapes, or
+//! a `{n}` literal. It is decoded with [`decode_bytes`], or with the
+//! [`MailboxCodec`] given to [`parse_line_with`] and [`parse_lines_with`] once UTF-8 is
+//! enabled, and if that fails, with [`decode_utf7_imap_lenient`], so a server that mangles
+//! a name does not lose the folder.
+//!
+//! # Usage:
+//!
+//! ```
+//! use utf7_imap::list::{parse_line, ListKind};
+//!
+//! let item = parse_line(br#"* LIST (\HasNoChildren) "/" "&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-""#).unwrap();
+//! assert_eq!(item.kind, ListKind::List);
+//! assert_eq!(item.flags, [r"\HasNoChildren"]);
+//! assert_eq!(item.delimiter, Some('/'));
+//! assert_eq!(item.name, b"&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-");
+//! assert_eq!(item.decoded, "Отправленные");
+//! ```
+//!
+//! [`decode_bytes`]: crate::decode_bytes
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::{decode_utf7_imap_lenient, DecodeError, MailboxCodec};
+
+/// Which command the response answers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListKind {
+    /// `* LIST`
+    List,
+    /// `* LSUB`
+    Lsub,
+}
+
+/// A parsed `LIST` or `LSUB` response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListResponse {
+    /// Which command the response answers.
+    pub kind: ListKind,
+    /// The name attributes, such as `\HasNoChildren`, as sent.
+    pub flags: Vec<String>,
+    /// The hierarchy delimiter, or `None` for `NIL`.
+    pub delimiter: Option<char>,
+    /// The encoded name, as sent, without quoting or escapes.
+    pub name: Vec<u8>,
+    /// The decoded name.
+    pub decoded: String,
+    /// Why the name could not be decoded as it should be, in which case
+    /// [`decoded`](ListResponse::decoded) holds the lenient decoding.
+    pub decode_error: Option<DecodeError>,
+}
+
+/// The kind of problem found while parsing a response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListErrorKind {
+    /// The line is not an untagged `LIST` or `LSUB` response.
+    NotListResponse,
+    /// Something else was found where the given item was expected.
+    Expected(&'static str),
+    /// The line ended before the response was complete.
+    UnexpectedEnd,
+}
+
+impl fmt::Display for ListErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListErrorKind::NotListResponse => f.write_str("not a LIST or LSUB response"),
+            ListErrorKind::Expected(what) => write!(f, "expected {}", what),
+            ListErrorKind::UnexpectedEnd => f.write_str("unexpected end of response"),
+        }
+    }
+}
+
+/// Error returned when a response cannot be parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ListError {
+    /// The kind of problem.
+    pub kind: ListErrorKind,
+    /// Line number, starting at 1, where the response starts.
+    pub line: usize,
+    /// Byte offset of the problem in the response.
+    pub position: usize,
+}
+
+impl fmt::Display for ListError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, byte {}",
+            self.kind, self.line, self.position
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ListError {}
+
+/// Parse a single response, with or without the trailing CRLF
+///
+/// A name sent as a literal follows the `{n}` and its CRLF in the same slice.
+pub fn parse_line(line: &[u8]) -> Result<ListResponse, ListError> {
+    parse_line_with(line, MailboxCodec::ModifiedUtf7)
+}
+
+/// Parse a single response, decoding the name with `codec`
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::list::parse_line_with;
+/// use utf7_imap::MailboxCodec;
+///
+/// let line = r#"* LIST () "/" "Отправленные""#.as_bytes();
+/// let item = parse_line_with(line, MailboxCodec::Utf8).unwrap();
+/// assert_eq!(item.decoded, "Отправленные");
+/// assert!(item.decode_error.is_none());
+/// ```
+pub fn parse_line_with(line: &[u8], codec: MailboxCodec) -> Result<ListResponse, ListError> {
+    let line = line
+        .strip_suffix(b"\n")
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .unwrap_or(line);
+    let mut parser = Parser {
+        input: line,
+        pos: 0,
+        codec,
+    };
+    parser.response().map_err(|kind| ListError {
+        kind,
+        line: 1,
+        position: parser.pos,
+    })
+}
+
+/// Parse every `LIST` and `LSUB` response in the server output
+///
+/// Other lines, such as the tagged completion, are skipped. A malformed response is
+/// reported as an error without stopping the iteration.
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::list::parse_lines;
+///
+/// let output = b"* LIST () \".\" INBOX\r\n\
+///     * LIST () \".\" {12}\r\nTom &- Jerry\r\n\
+///     * LIST (\\Noselect \".\" Broken\r\n\
+///     A1 OK LIST completed\r\n";
+/// let items: Vec<_> = parse_lines(output).collect();
+/// assert_eq!(items.len(), 3);
+/// assert_eq!(items[1].as_ref().unwrap().decoded, "Tom & Jerry");
+/// assert_eq!(items[2].as_ref().unwrap_err().line, 4);
+/// ```
+pub fn parse_lines(output: &[u8]) -> ListResponses<'_> {
+    parse_lines_with(output, MailboxCodec::ModifiedUtf7)
+}
+
+/// Parse every `LIST` and `LSUB` response in the server output, decoding the names with
+/// `codec`
+pub fn parse_lines_with(output: &[u8], codec: MailboxCodec) -> ListResponses<'_> {
+    ListResponses {
+        rest: output,
+        line: 1,
+        codec,
+    }
+}
+
+/// Iterator returned by [`parse_lines`] and [`parse_lines_with`]
+#[derive(Debug, Clone)]
+pub struct ListResponses<'a> {
+    rest: &'a [u8],
+    line: usize,
+    codec: MailboxCodec,
+}
+
+impl Iterator for ListResponses<'_> {
+    type Item = Result<ListResponse, ListError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.rest.is_empty() {
+            let len = response_len(self.rest);
+            let (response, rest) = self.rest.split_at(len);
+            let line = self.line;
+            self.rest = rest;
+            self.line += response.iter().filter(|&&byte| byte == b'\n').count();
+            match parse_line_with(response, self.codec) {
+                Err(ListError {
+                    kind: ListErrorKind::NotListResponse,
+                    ..
+                }) => continue,
+                result => return Some(result.map_err(|err| ListError { line, ..err })),
+            }
+        }
+        None
+    }
+}
+
+/// Length of the first response in `output`, including its literals and final newline.
+fn response_len(output: &[u8]) -> usize {
+    let mut start = 0;
+    loop {
+        let Some(newline) = output[start..].iter().position(|&byte| byte == b'\n') else {
+            return output.len();
+        };
+        let end = start + newline + 1;
+        let line = &output[start..start + newline];
+        match literal_len(line.strip_suffix(b"\r").unwrap_or(line)) {
+            Some(len) => start = end.saturating_add(len).min(output.len()),
+            None => return end,
+        }
+    }
+}
+
+/// The `n` of a `{n}` that ends `line`.
+fn literal_len(line: &[u8]) -> Option<usize> {
+    let open = line.strip_suffix(b"}")?.iter().rposition(|&b| b == b'{')?;
+    parse_number(&line[open + 1..line.len() - 1])
+}
+
+fn parse_number(digits: &[u8]) -> Option<usize> {
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    core::str::from_utf8(digits).ok()?.parse().ok()
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    codec: MailboxCodec,
+}
+
+impl Parser<'_> {
+    fn response(&mut self) -> Result<ListResponse, ListErrorKind> {
+        let kind = if self.keyword(b"* LIST ") {
+            ListKind::List
+        } else if self.keyword(b"* LSUB ") {
+            ListKind::Lsub
+        } else {
+            return Err(ListErrorKind::NotListResponse);
+        };
+        let flags = self.flags()?;
+        self.byte(b' ', "' '")?;
+        let delimiter = self.delimiter()?;
+        self.byte(b' ', "' '")?;
+        let name = self.astring()?;
+        // RFC 5258 extended data may follow the name.
+        if self.keyword(b" (") {
+            self.extended_data()?;
+        }
+        if self.pos < self.input.len() {
+            return Err(ListErrorKind::Expected("end of line"));
+        }
+        let (decoded, decode_error) = match self.codec.decode_bytes(&name) {
+            Ok(decoded) => (decoded, None),
+            Err(err) => {
+                let report = decode_utf7_imap_lenient(&String::from_utf8_lossy(&name));
+                (report.text, Some(err))
+            }
+        };
+        Ok(ListResponse {
+            kind,
+            flags,
+            delimiter,
+            name,
+            decoded,
+            decode_error,
+        })
+    }
+
+    fn flags(&mut self) -> Result<Vec<String>, ListErrorKind> {
+        self.byte(b'(', "'('")?;
+        let mut flags = Vec::new();
+        loop {
+            if self.peek()? == b')' {
+                self.pos += 1;
+                return Ok(flags);
+            }
+            if !flags.is_empty() {
+                self.byte(b' ', "' ' or ')'")?;
+            }
+            let flag = self.take_while(|byte| !matches!(byte, b' ' | b'(' | b')' | b'"'));
+            if flag.is_empty() {
+                return Err(ListErrorKind::Expected("flag"));
+            }
+            flags.push(String::from_utf8_lossy(flag).into_owned());
+        }
+    }
+
+    /// Skip extended data up to the `)` that closes its opening `(`.
+    fn extended_data(&mut self) -> Result<(), ListErrorKind> {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.peek()? {
+                b'"' => {
+                    self.quoted()?;
+                }
+                b'{' => {
+                    self.literal()?;
+                }
+                byte => {
+                    match byte {
+                        b'(' => depth += 1,
+                        b')' => depth -= 1,
+                        _ => {}
+                    }
+                    self.pos += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn delimiter(&mut self) -> Result<Option<char>, ListErrorKind> {
+        if self.keyword(b"NIL") {
+            return Ok(None);
+        }
+        let start = self.pos;
+        match self.quoted()?.as_slice() {
+            &[byte] if byte.is_ascii() => Ok(Some(char::from(byte))),
+            _ => {
+                self.pos = start;
+                Err(ListErrorKind::Expected("delimiter"))
+            }
+        }
+    }
+
+    fn astring(&mut self) -> Result<Vec<u8>, ListErrorKind> {
+        match self.peek()? {
+            b'"' => self.quoted(),
+            b'{' => self.literal(),
+            _ => {
+                let atom = self.take_while(|byte| !matches!(byte, b' ' | b'(' | b')'));
+                if atom.is_empty() {
+                    return Err(ListErrorKind::Expected("mailbox name"));
+                }
+                Ok(atom.to_vec())
+            }
+        }
+    }
+
+    fn quoted(&mut self) -> Result<Vec<u8>, ListErrorKind> {
+        self.byte(b'"', "'\"'")?;
+        let mut text = Vec::new();
+        loop {
+            match self.next()? {
+                b'"' => return Ok(text),
+                b'\\' => match self.next()? {
+                    byte @ (b'"' | b'\\') => text.push(byte),
+                    _ => {
+                        self.pos -= 1;
+                        return Err(ListErrorKind::Expected("'\"' or '\\' after '\\'"));
+                    }
+                },
+                byte => text.push(byte),
+            }
+        }
+    }
+
+    fn literal(&mut self) -> Result<Vec<u8>, ListErrorKind> {
+        self.byte(b'{', "'{'")?;
+        let digits = self.take_while(|byte| byte.is_ascii_digit());
+        let len = parse_number(digits).ok_or(ListErrorKind::Expected("literal length"))?;
+        self.byte(b'}', "'}'")?;
+        if self.peek()? == b'\r' {
+            self.pos += 1;
+        }
+        self.byte(b'\n', "line break after literal length")?;
+        let rest = &self.input[self.pos..];
+        if rest.len() < len {
+            self.pos = self.input.len();
+            return Err(ListErrorKind::UnexpectedEnd);
+        }
+        self.pos += len;
+        Ok(rest[..len].to_vec())
+    }
+
+    /// Consume `keyword`, ignoring ASCII case, if it comes next.
+    fn keyword(&mut self, keyword: &[u8]) -> bool {
+        let rest = &self.input[self.pos..];
+        let found =
+            rest.len() >= keyword.len() && rest[..keyword.len()].eq_ignore_ascii_case(keyword);
+        if found {
+            self.pos += keyword.len();
+        }
+        found
+    }
+
+    fn byte(&mut self, expected: u8, what: &'static str) -> Result<(), ListErrorKind> {
+        if self.peek()? != expected {
+            return Err(ListErrorKind::Expected(what));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn peek(&self) -> Result<u8, ListErrorKind> {
+        self.input
+            .get(self.pos)
+            .copied()
+            .ok_or(ListErrorKind::UnexpectedEnd)
+    }
+
+    fn next(&mut self) -> Result<u8, ListErrorKind> {
+        let byte = self.peek()?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn take_while(&mut self, accept: impl Fn(u8) -> bool) -> &[u8] {
+        let start = self.pos;
+        while self.input.get(self.pos).is_some_and(|&byte| accept(byte)) {
+            self.pos += 1;
+        }
+        &self.input[start..self.pos]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DecodeErrorKind;
+
+    #[test]
+    fn name_forms() {
+        let item =
+            parse_line(b"* lsub (\\Noselect \\HasChildren) NIL \"a \\\"b\\\" \\\\c\"\r\n").unwrap();
+        assert_eq!(item.kind, ListKind::Lsub);
+        assert_eq!(item.flags, ["\\Noselect", "\\HasChildren"]);
+        assert_eq!(item.delimiter, None);
+        assert_eq!(item.decoded, "a \"b\" \\c");
+
+        let item = parse_line(b"* LIST () \"\\\\\" {8}\r\ncaf&AOk-").unwrap();
+        assert_eq!(item.delimiter, Some('\\'));
+        assert_eq!(item.decoded, "caf\u{e9}");
+
+        // RFC 5258 extended data is skipped
+        let item = parse_line(b"* LIST (\\Subscribed) \"/\" Foo (\"CHILDINFO\" (\"SUBSCRIBED\"))")
+            .unwrap();
+        assert_eq!(item.name, b"Foo");
+    }
+
+    #[test]
+    fn lenient_fallback() {
+        let item = parse_line("* LIST () \"/\" \"Входящие/&BB4EQgQ/BEA-\"".as_bytes()).unwrap();
+        assert_eq!(item.decoded, "Входящие/Отпр");
+        assert_eq!(
+            item.decode_error.map(|err| err.kind()),
+            Some(DecodeErrorKind::UnshiftedByte(0xd0))
+        );
+
+        let item = parse_line("* LIST () \"/\" \"Входящие\"".as_bytes()).unwrap();
+        let err = item.decode_error.unwrap();
+        assert_eq!(err.sequence(), 0..2);
+        assert_eq!(
+            err.snippet().to_string(),
+            "byte 0xd0 is not printable US-ASCII at byte 0\n  Входящие\n  ^"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let err = |line: &[u8]| {
+            parse_line(line)
+                .map(drop)
+                .map_err(|err| (err.kind, err.position))
+        };
+        assert_eq!(
+            err(b"* LIST \\HasChildren \"/\" a"),
+            Err((ListErrorKind::Expected("'('"), 7))
+        );
+        assert_eq!(
+            err(b"* LIST () \"//\" a"),
+            Err((ListErrorKind::Expected("delimiter"), 10))
+        );
+        assert_eq!(
+            err(b"* LIST () \"/\" \"a\\b\""),
+            Err((ListErrorKind::Expected("'\"' or '\\' after '\\'"), 17))
+        );
+        assert_eq!(
+            err(b"* LIST () \"/\" {9}\r\nshort"),
+            Err((ListErrorKind::UnexpectedEnd, 24))
+        );
+        assert_eq!(err(b"* 3 EXISTS"), Err((ListErrorKind::NotListResponse, 0)));
+        assert_eq!(
+            err(b"* LIST (\\HasNoChildren) \"/\" My Folder"),
+            Err((ListErrorKind::Expected("end of line"), 30))
+        );
+        assert_eq!(
+            err(b"* LIST () \"/\" INBOX extra"),
+            Err((ListErrorKind::Expected("end of line"), 19))
+        );
+        assert_eq!(
+            err(b"* LIST () \"/\" Foo (\"CHILDINFO\" (\")\")"),
+            Err((ListErrorKind::UnexpectedEnd, 36))
+        );
+        assert_eq!(
+            err(b"* LIST () \"/\" Foo (\"CHILDINFO\") x"),
+            Err((ListErrorKind::Expected("end of line"), 31))
+        );
+
+        // a literal's line break does not end the response
+        let items: Vec<_> =
+            parse_lines(b"* LIST () \"/\" {3}\r\n* L\r\n* LIST ( \"/\" x\r\n").collect();
+        assert_eq!(items[0].as_ref().unwrap().decoded, "* L");
+        assert_eq!(items[1].as_ref().unwrap_err().line, 3);
+        assert_eq!(items.len(), 2);
+    }
+
+    #[test]
+    fn utf8_names() {
+        let output = "* LIST () \"/\" &AOk-\r\n* LIST () \"/\" \"Входящие\"\r\n".as_bytes();
+        let items: Vec<_> = parse_lines_with(output, MailboxCodec::Utf8)
+            .map(|item| item.unwrap())
+            .collect();
+        assert_eq!(items[0].decoded, "\u{e9}");
+        assert_eq!(items[1].decoded, "Входящие");
+        assert!(items[1].decode_error.is_none());
+
+        let item = parse_line_with(b"* LIST () \"/\" \"caf\xe9\"", MailboxCodec::Utf8).unwrap();
+        assert_eq!(item.decoded, "caf\u{fffd}");
+        assert_eq!(
+            item.decode_error.map(|err| err.kind()),
+            Some(DecodeErrorKind::UnshiftedByte(0xe9))
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use alloc::string::String;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+
+use crate::{DecodeError, Utf7ImapStr, Utf7ImapString};
+
+/// Mailbox name in both its encoded wire form and its decoded Unicode form
+///
+/// Use the [encoded](MailboxName::encoded) form in IMAP commands and the
+/// [decoded](MailboxName::decoded) form in user interfaces and logs.
+///
+/// Names compare and hash by their decoded form. As RFC 3501 §5.1 requires, `INBOX` is
+/// case-insensitive, while other names are case-sensitive.
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::MailboxName;
+///
+/// let sent = MailboxName::from_wire("&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-").unwrap();
+/// assert_eq!(sent.decoded(), "Отправленные");
+/// assert_eq!(
+///     format!("{:?}", sent),
+///     r#""Отправленные" (&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-)"#
+/// );
+///
+/// assert_eq!(MailboxName::from_unicode("inbox"), MailboxName::from_unicode("INBOX"));
+/// assert_ne!(MailboxName::from_unicode("drafts"), MailboxName::from_unicode("Drafts"));
+/// ```
+#[derive(Clone)]
+pub struct MailboxName {
+    encoded: Utf7ImapString,
+    decoded: String,
+}
+
+impl MailboxName {
+    /// Name a mailbox by its Unicode name.
+    pub fn from_unicode(name: &str) -> Self {
+        MailboxName {
+            encoded: Utf7ImapString::encode(name),
+            decoded: String::from(name),
+        }
+    }
+
+    /// Name a mailbox by its encoded name, as a server sends it.
+    ///
+    /// The encoded form is kept as it is, even if it is not the canonical encoding.
+    pub fn from_wire(name: &str) -> Result<Self, DecodeError> {
+        Utf7ImapStr::new(name).map(MailboxName::from)
+    }
+
+    /// The encoded name, for IMAP commands.
+    pub fn encoded(&self) -> &Utf7ImapStr {
+        &self.encoded
+    }
+
+    /// The decoded name, for display.
+    pub fn decoded(&self) -> &str {
+        &self.decoded
+    }
+
+    /// Take the encoded name.
+    pub fn into_encoded(self) -> Utf7ImapString {
+        self.encoded
+    }
+
+    /// Take the decoded name.
+    pub fn into_decoded(self) -> String {
+        self.decoded
+    }
+
+    /// Whether this is `INBOX`, in any case.
+    pub fn is_inbox(&self) -> bool {
+        self.decoded.eq_ignore_ascii_case("INBOX")
+    }
+
+    /// The decoded name with `INBOX` in upper case.
+    fn key(&self) -> &str {
+        if self.is_inbox() {
+            "INBOX"
+        } else {
+            &self.decoded
+        }
+    }
+}
+
+impl From<&Utf7ImapStr> for MailboxName {
+    fn from(name: &Utf7ImapStr) -> Self {
+        MailboxName {
+            decoded: name.decode().into_owned(),
+            encoded: Utf7ImapString::from(name),
+        }
+    }
+}
+
+impl From<Utf7ImapString> for MailboxName {
+    fn from(name: Utf7ImapString) -> Self {
+        MailboxName {
+            decoded: name.decode().into_owned(),
+            encoded: name,
+        }
+    }
+}
+
+impl From<&str> for MailboxName {
+    fn from(name: &str) -> Self {
+        MailboxName::from_unicode(name)
+    }
+}
+
+impl fmt::Debug for MailboxName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} ({})", self.decoded, self.encoded)
+    }
+}
+
+impl fmt::Display for MailboxName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.decoded)
+    }
+}
+
+impl PartialEq for MailboxName {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for MailboxName {}
+
+impl Hash for MailboxName {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+impl PartialOrd for MailboxName {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MailboxName {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(other.key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn inbox_is_case_insensitive() {
+        let names: HashSet<_> = ["INBOX", "inbox", "InBoX", "Inbox.Archive", "inbox.archive"]
+            .into_iter()
+            .map(MailboxName::from_unicode)
+            .collect();
+        assert_eq!(names.len(), 3);
+        assert!(MailboxName::from_unicode("iNbOx").is_inbox());
+        assert!(!MailboxName::from_unicode("INBOX/Sent").is_inbox());
+    }
+
+    #[test]
+    fn both_forms() {
+        let name = MailboxName::from_unicode("Tom & Jerry");
+        assert_eq!(name.encoded().as_str(), "Tom &- Jerry");
+        assert_eq!(name.to_string(), "Tom & Jerry");
+        assert_eq!(format!("{:?}", name), r#""Tom & Jerry" (Tom &- Jerry)"#);
+
+        // a non-canonical encoding is kept, and still equal to the canonical one
+        let wire = MailboxName::from_wire("th&AOk-&AOI-tre").unwrap();
+        assert_eq!(wire.encoded().as_str(), "th&AOk-&AOI-tre");
+        assert_eq!(wire, MailboxName::from_unicode("th\u{e9}\u{e2}tre"));
+        assert!(MailboxName::from_wire("Tom & Jerry").is_err());
+    }
+}
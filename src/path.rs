@@ -0,0 +1,202 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::{decode, is_ascii_custom_char, PathError, Utf7ImapString};
+
+/// Hierarchical mailbox name, held as decoded segments and the server's delimiter
+///
+/// Each segment is encoded on its own and the segments are joined with the delimiter,
+/// which is never encoded. A segment cannot contain the delimiter, since the server would
+/// read it as one more level.
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::MailboxPath;
+///
+/// let path = MailboxPath::new(["Архив", "2024", "Отчёты"], '.').unwrap();
+/// assert_eq!(path.encode().as_str(), "&BBAEQARFBDgEMg-.2024.&BB4EQgRHBFEEQgRL-");
+///
+/// let parsed = MailboxPath::from_wire("&BBAEQARFBDgEMg-.2024.&BB4EQgRHBFEEQgRL-", '.').unwrap();
+/// assert_eq!(parsed.segments(), ["Архив", "2024", "Отчёты"]);
+///
+/// assert!(MailboxPath::new(["Archive", "v1.2"], '.').is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MailboxPath {
+    segments: Vec<String>,
+    delimiter: char,
+}
+
+impl MailboxPath {
+    /// Build a path from decoded segments.
+    pub fn new<I, S>(segments: I, delimiter: char) -> Result<Self, PathError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        check_delimiter(delimiter)?;
+        let mut path = MailboxPath {
+            segments: Vec::new(),
+            delimiter,
+        };
+        for segment in segments {
+            path.push(segment)?;
+        }
+        Ok(path)
+    }
+
+    /// Split an encoded name, as a server sends it, into decoded segments.
+    ///
+    /// Only delimiters outside shift sequences split the name. A shift sequence that
+    /// decodes to the delimiter is reported as [`PathError::DelimiterInSegment`].
+    pub fn from_wire(name: &str, delimiter: char) -> Result<Self, PathError> {
+        check_delimiter(delimiter)?;
+        // Decode the whole name first, for errors with positions in the whole name.
+        decode(name)?;
+        let mut path = MailboxPath {
+            segments: Vec::new(),
+            delimiter,
+        };
+        if name.is_empty() {
+            return Ok(path);
+        }
+        for segment in split_wire(name, delimiter) {
+            path.push(decode(segment)?)?;
+        }
+        Ok(path)
+    }
+
+    /// Append a decoded segment.
+    pub fn push<S: Into<String>>(&mut self, segment: S) -> Result<(), PathError> {
+        let segment = segment.into();
+        if segment.contains(self.delimiter) {
+            return Err(PathError::DelimiterInSegment {
+                index: self.segments.len(),
+                delimiter: self.delimiter,
+            });
+        }
+        self.segments.push(segment);
+        Ok(())
+    }
+
+    /// The decoded segments, from the top of the hierarchy down.
+    pub fn segments(&self) -> &[String] {
+        &self.segments
+    }
+
+    /// The hierarchy delimiter.
+    pub fn delimiter(&self) -> char {
+        self.delimiter
+    }
+
+    /// The encoded name, for IMAP commands.
+    pub fn encode(&self) -> Utf7ImapString {
+        // The delimiter is printable US-ASCII, so it ends any shift sequence and encoding
+        // the joined name is the same as joining the encoded segments.
+        Utf7ImapString::encode(&self.to_string())
+    }
+}
+
+/// Shows the decoded segments joined with the delimiter.
+impl fmt::Display for MailboxPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            if i > 0 {
+                fmt::Write::write_char(f, self.delimiter)?;
+            }
+            f.write_str(segment)?;
+        }
+        Ok(())
+    }
+}
+
+/// Split an encoded name at the delimiters outside shift sequences.
+fn split_wire(name: &str, delimiter: char) -> impl Iterator<Item = &str> {
+    let mut shifted = false;
+    name.split(move |c| {
+        let split = c == delimiter && !shifted;
+        match c {
+            '&' if !shifted => shifted = true,
+            '-' if shifted => shifted = false,
+            _ => {}
+        }
+        split
+    })
+}
+
+/// The delimiter must stay literal in the encoded name and must not start a shift.
+fn check_delimiter(delimiter: char) -> Result<(), PathError> {
+    if is_ascii_custom_char(delimiter) && delimiter != '&' {
+        Ok(())
+    } else {
+        Err(PathError::InvalidDelimiter(delimiter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_round_trip() {
+        let path = MailboxPath::new(["INBOX", "Tom & Jerry", "caf\u{e9}"], '/').unwrap();
+        let wire = path.encode();
+        assert_eq!(wire.as_str(), "INBOX/Tom &- Jerry/caf&AOk-");
+        assert_eq!(MailboxPath::from_wire(wire.as_str(), '/').unwrap(), path);
+        assert_eq!(path.to_string(), "INBOX/Tom & Jerry/caf\u{e9}");
+
+        // `,` is in the modified BASE64 alphabet, but only literal ones split the name
+        let path = MailboxPath::from_wire("&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-,a", ',').unwrap();
+        assert_eq!(path.segments(), ["Отправленные", "a"]);
+
+        // the `-` that ends a shift sequence is not a delimiter
+        let path = MailboxPath::from_wire("caf&AOk--&-", '-').unwrap();
+        assert_eq!(path.segments(), ["caf\u{e9}", "&"]);
+        assert_eq!(path.encode().as_str(), "caf&AOk--&-");
+
+        let empty = MailboxPath::new(Vec::<String>::new(), '.').unwrap();
+        assert_eq!(empty.encode().as_str(), "");
+        assert_eq!(MailboxPath::from_wire("", '.').unwrap(), empty);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert_eq!(
+            MailboxPath::new(["a", "b.c"], '.'),
+            Err(PathError::DelimiterInSegment {
+                index: 1,
+                delimiter: '.'
+            })
+        );
+        assert_eq!(
+            MailboxPath::new(["a"], '&'),
+            Err(PathError::InvalidDelimiter('&'))
+        );
+        assert_eq!(
+            MailboxPath::new(["a"], '\u{b7}'),
+            Err(PathError::InvalidDelimiter('\u{b7}'))
+        );
+        assert!(matches!(
+            MailboxPath::from_wire("a/&AOl-", '/'),
+            Err(PathError::Decode(_))
+        ));
+
+        // an encoded delimiter is not a split, and cannot be part of a segment either
+        assert_eq!(
+            MailboxPath::from_wire("a&AC4-b", '.'),
+            Err(PathError::DelimiterInSegment {
+                index: 0,
+                delimiter: '.'
+            })
+        );
+        assert_eq!(
+            MailboxPath::from_wire("x.a&AC4-b", '.'),
+            Err(PathError::DelimiterInSegment {
+                index: 1,
+                delimiter: '.'
+            })
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! Serialize mailbox names as Unicode and deserialize them from either form
+//!
+//! Use this module with `#[serde(with = "utf7_imap::serde")]` on a `String` field that
+//! holds an encoded name. The decoded name is written, so that files edited by hand
+//! contain readable names. On reading, a name that is already well-formed modified UTF-7
+//! is kept as it is, and any other name is encoded.
+//!
+//! [`Utf7ImapString`], [`Utf7ImapStr`], [`MailboxName`] and [`CanonicalName`] are
+//! serialized and deserialized the same way. A [`CanonicalName`] can hold unpaired
+//! surrogates, which are written as U+FFFD. A [`MailboxPath`] is written as a struct of
+//! its decoded `segments` and its `delimiter`.
+//!
+//! # Usage:
+//!
+//! ```
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Folder {
+//!     #[serde(with = "utf7_imap::serde")]
+//!     name: String,
+//! }
+//!
+//! let folder: Folder = serde_json::from_str(r#"{"name": "Отправленные"}"#).unwrap();
+//! assert_eq!(folder.name, "&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-");
+//! assert_eq!(serde_json::to_string(&folder).unwrap(), r#"{"name":"Отправленные"}"#);
+//!
+//! let folder: Folder = serde_json::from_str(r#"{"name": "&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-"}"#).unwrap();
+//! assert_eq!(folder.name, "&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-");
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use serde::de::{Error as _, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Error as _, SerializeStruct};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    decode, decode_utf7_imap_with, CanonicalName, DecodeOptions, MailboxName, MailboxPath,
+    SurrogatePolicy, Utf7ImapStr, Utf7ImapString,
+};
+
+/// Write the decoded form of an encoded name.
+pub fn serialize<S: Serializer>(name: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    let decoded = decode(name).map_err(S::Error::custom)?;
+    serializer.serialize_str(&decoded)
+}
+
+/// Read a name in either form and return the encoded form.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Utf7ImapString::deserialize(deserializer).map(Utf7ImapString::into_string)
+}
+
+impl Serialize for Utf7ImapStr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.decode())
+    }
+}
+
+impl Serialize for Utf7ImapString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.as_utf7_imap_str().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Utf7ImapString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Utf7ImapString::from_either_form)
+    }
+}
+
+impl Serialize for MailboxName {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.decoded())
+    }
+}
+
+impl<'de> Deserialize<'de> for MailboxName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Utf7ImapString::deserialize(deserializer).map(MailboxName::from)
+    }
+}
+
+impl Serialize for CanonicalName {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let options = DecodeOptions::new().surrogates(SurrogatePolicy::Replace);
+        let report = decode_utf7_imap_with(self.as_str(), &options).map_err(S::Error::custom)?;
+        serializer.serialize_str(&report.text)
+    }
+}
+
+impl<'de> Deserialize<'de> for CanonicalName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = Utf7ImapString::deserialize(deserializer)?;
+        CanonicalName::new(name.as_str()).map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for MailboxPath {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut path = serializer.serialize_struct("MailboxPath", 2)?;
+        path.serialize_field("segments", self.segments())?;
+        path.serialize_field("delimiter", &self.delimiter())?;
+        path.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for MailboxPath {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_struct("MailboxPath", PATH_FIELDS, PathVisitor)
+    }
+}
+
+const PATH_FIELDS: &[&str] = &["segments", "delimiter"];
+
+struct PathVisitor;
+
+impl PathVisitor {
+    fn path<E: serde::de::Error>(
+        segments: Option<Vec<String>>,
+        delimiter: Option<char>,
+    ) -> Result<MailboxPath, E> {
+        let segments = segments.ok_or_else(|| E::missing_field("segments"))?;
+        let delimiter = delimiter.ok_or_else(|| E::missing_field("delimiter"))?;
+        MailboxPath::new(segments, delimiter).map_err(E::custom)
+    }
+}
+
+impl<'de> Visitor<'de> for PathVisitor {
+    type Value = MailboxPath;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("mailbox path segments and delimiter")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let segments = seq.next_element()?;
+        let delimiter = seq.next_element()?;
+        Self::path(segments, delimiter)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let (mut segments, mut delimiter) = (None, None);
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "segments" if segments.is_some() => {
+                    return Err(A::Error::duplicate_field("segments"))
+                }
+                "delimiter" if delimiter.is_some() => {
+                    return Err(A::Error::duplicate_field("delimiter"))
+                }
+                "segments" => segments = Some(map.next_value()?),
+                "delimiter" => delimiter = Some(map.next_value()?),
+                _ => return Err(A::Error::unknown_field(&key, PATH_FIELDS)),
+            }
+        }
+        Self::path(segments, delimiter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_types_round_trip() {
+        let name: MailboxName = serde_json::from_str(r#""th&AOk-&AOI-tre""#).unwrap();
+        assert_eq!(name.encoded().as_str(), "th&AOk-&AOI-tre");
+        assert_eq!(serde_json::to_string(&name).unwrap(), r#""théâtre""#);
+
+        let name: CanonicalName = serde_json::from_str(r#""th&AOk-&AOI-tre""#).unwrap();
+        assert_eq!(name.as_str(), "th&AOkA4g-tre");
+        let name: CanonicalName = serde_json::from_str(r#""théâtre""#).unwrap();
+        assert_eq!(name.as_str(), "th&AOkA4g-tre");
+        assert_eq!(serde_json::to_string(&name).unwrap(), r#""théâtre""#);
+
+        let name = CanonicalName::new("&2D0-").unwrap();
+        assert_eq!(serde_json::to_string(&name).unwrap(), "\"\u{fffd}\"");
+
+        // not well-formed, so taken as a decoded name
+        let name: Utf7ImapString = serde_json::from_str(r#""Tom & Jerry""#).unwrap();
+        assert_eq!(name.as_str(), "Tom &- Jerry");
+        assert_eq!(serde_json::to_string(&name).unwrap(), r#""Tom & Jerry""#);
+    }
+
+    #[test]
+    fn path_round_trip() {
+        let path = MailboxPath::new(["Архив", "2024"], '.').unwrap();
+        let json = serde_json::to_string(&path).unwrap();
+        assert_eq!(json, r#"{"segments":["Архив","2024"],"delimiter":"."}"#);
+        assert_eq!(serde_json::from_str::<MailboxPath>(&json).unwrap(), path);
+
+        let err = serde_json::from_str::<MailboxPath>(r#"{"segments":["v1.2"],"delimiter":"."}"#);
+        assert!(err.is_err());
+        let err = serde_json::from_str::<MailboxPath>(r#"{"segments":["a"]}"#);
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn with_module_rejects_malformed_field() {
+        #[derive(::serde::Serialize)]
+        struct Folder {
+            #[serde(with = "crate::serde")]
+            name: String,
+        }
+
+        let folder = Folder {
+            name: String::from("&AOl-"),
+        };
+        assert!(serde_json::to_string(&folder).is_err());
+    }
+}
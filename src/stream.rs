@@ -0,0 +1,456 @@
+use alloc::string::String;
+
+use crate::decode::surrogate_pair;
+use crate::{
+    is_ascii_custom, is_ascii_custom_char, DecodeError, DecodeErrorKind, INVALID_BASE64,
+    MODIFIED_BASE64, MODIFIED_BASE64_VALUES,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Direct,
+    /// Just after the `&` that starts a shift sequence.
+    ShiftStart,
+    Shifted,
+}
+
+/// Decoder for UTF-7 IMAP mailbox names that arrive in several chunks
+///
+/// Modelled on the `Decoder` of the `encoding_rs` crate, it keeps partial modified BASE64 quanta and the
+/// first half of surrogate pairs between calls. Bytes outside printable US-ASCII are
+/// rejected. After the last chunk of a name, or after an error, the decoder is ready
+/// for the next name.
+///
+/// The characters of a shift sequence are only written once its closing `-` has been
+/// checked, so a sequence that fails writes nothing. Errors hold the name up to the
+/// offending byte, and their kinds and positions, byte offsets in the whole name, are
+/// those of [`try_decode_utf7_imap`]. The one exception is a character that cannot be in a
+/// shift sequence, which is reported as soon as it arrives, where the one-shot decoder
+/// reports an unterminated sequence if no `-` follows.
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::Utf7ImapDecoder;
+///
+/// let mut decoder = Utf7ImapDecoder::new();
+/// let mut name = String::new();
+/// decoder.decode_to_string(b"Drafts/&BB4EQ", &mut name, false).unwrap();
+/// decoder.decode_to_string(b"gQ,BEA-", &mut name, true).unwrap();
+/// assert_eq!(name, "Drafts/Отпр");
+/// ```
+///
+/// [`try_decode_utf7_imap`]: crate::try_decode_utf7_imap
+#[derive(Debug, Clone)]
+pub struct Utf7ImapDecoder {
+    state: State,
+    /// Bits of the current code unit decoded so far.
+    bits: u32,
+    buffer: u32,
+    /// Pending high surrogate, with the offset of the character holding its first bit.
+    high_surrogate: Option<(usize, u16)>,
+    /// First unpaired surrogate of the current shift sequence, reported once the rest of
+    /// the sequence has been checked.
+    unpaired: Option<(usize, u16)>,
+    /// Characters of the current shift sequence, written once it has been closed.
+    pending: String,
+    /// Text of the name so far, for error reporting.
+    name: String,
+    /// Offset in `name` of the `&` of the current shift sequence.
+    sequence_start: usize,
+}
+
+impl Default for Utf7ImapDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Utf7ImapDecoder {
+    /// Create a decoder that accepts the same names as [`try_decode_utf7_imap`].
+    ///
+    /// [`try_decode_utf7_imap`]: crate::try_decode_utf7_imap
+    pub fn new() -> Self {
+        Utf7ImapDecoder {
+            state: State::Direct,
+            bits: 0,
+            buffer: 0,
+            high_surrogate: None,
+            unpaired: None,
+            pending: String::new(),
+            name: String::new(),
+            sequence_start: 0,
+        }
+    }
+
+    /// Decode the next chunk of a name, appending the result to `dst`.
+    ///
+    /// Set `last` for the final chunk, so that an unterminated shift sequence is reported.
+    /// On error `dst` holds what was decoded before the sequence or byte that failed.
+    pub fn decode_to_string(
+        &mut self,
+        src: &[u8],
+        dst: &mut String,
+        last: bool,
+    ) -> Result<(), DecodeError> {
+        let result = self.decode_chunk(src, dst, last);
+        if result.is_err() || last {
+            *self = Self::new();
+        }
+        result
+    }
+
+    fn decode_chunk(
+        &mut self,
+        src: &[u8],
+        dst: &mut String,
+        last: bool,
+    ) -> Result<(), DecodeError> {
+        for &byte in src {
+            match (self.state, byte) {
+                (State::Direct, b'&') => {
+                    self.state = State::ShiftStart;
+                    self.sequence_start = self.name.len();
+                    self.name.push('&');
+                }
+                (State::Direct, _) if is_ascii_custom(byte) => {
+                    self.name.push(char::from(byte));
+                    dst.push(char::from(byte));
+                }
+                (State::ShiftStart, b'-') => {
+                    self.name.push('-');
+                    dst.push('&');
+                    self.state = State::Direct;
+                }
+                (State::Shifted, b'-') => {
+                    self.name.push('-');
+                    self.end_shift()?;
+                    dst.push_str(&self.pending);
+                    self.pending.clear();
+                }
+                (State::ShiftStart | State::Shifted, _) => {
+                    let value = match MODIFIED_BASE64_VALUES[usize::from(byte)] {
+                        INVALID_BASE64 => return Err(self.error_at(Self::invalid_byte(byte), byte)),
+                        value => u32::from(value),
+                    };
+                    self.name.push(char::from(byte));
+                    self.state = State::Shifted;
+                    self.push_sextet(value);
+                }
+                (State::Direct, _) => {
+                    return Err(self.error_at(DecodeErrorKind::UnshiftedByte(byte), byte))
+                }
+            }
+        }
+        if last && self.state != State::Direct {
+            return Err(self.error(DecodeErrorKind::UnterminatedShift, self.sequence_start));
+        }
+        Ok(())
+    }
+
+    fn push_sextet(&mut self, value: u32) {
+        self.buffer = (self.buffer << 6) | value;
+        self.bits += 6;
+        if self.bits < 16 {
+            return;
+        }
+        self.bits -= 16;
+        let unit = (self.buffer >> self.bits) as u16;
+        self.buffer &= (1 << self.bits) - 1;
+        match (self.high_surrogate.take(), unit) {
+            (None, 0xd800..=0xdbff) => self.high_surrogate = Some((self.unit_start(), unit)),
+            (Some((_, high)), 0xdc00..=0xdfff) => self.pending.push(surrogate_pair(high, unit)),
+            (Some(high), _) => {
+                self.unpaired.get_or_insert(high);
+            }
+            (None, 0xdc00..=0xdfff) => {
+                let start = self.unit_start();
+                self.unpaired.get_or_insert((start, unit));
+            }
+            (None, _) => self.pending.extend(char::from_u32(u32::from(unit))),
+        }
+    }
+
+    /// Offset in the name of the character holding the first bit of the code unit just
+    /// completed.
+    fn unit_start(&self) -> usize {
+        let units = (self.name.len() - self.sequence_start - 1) * 6 / 16;
+        self.sequence_start + 1 + (units - 1) * 16 / 6
+    }
+
+    fn end_shift(&mut self) -> Result<(), DecodeError> {
+        let last = self.name.len() - 2;
+        let byte_bits = self.bits % 8;
+        if byte_bits >= 6 || self.buffer & ((1 << byte_bits) - 1) != 0 {
+            return Err(self.error(DecodeErrorKind::BadPadding, last));
+        }
+        if self.bits >= 8 {
+            return Err(self.error(DecodeErrorKind::OddByteCount, last));
+        }
+        if let Some((start, unit)) = self.unpaired.or(self.high_surrogate) {
+            return Err(self.error(DecodeErrorKind::UnpairedSurrogate(unit), start));
+        }
+        self.state = State::Direct;
+        self.bits = 0;
+        self.buffer = 0;
+        Ok(())
+    }
+
+    fn invalid_byte(byte: u8) -> DecodeErrorKind {
+        if byte.is_ascii() {
+            DecodeErrorKind::InvalidBase64Char(char::from(byte))
+        } else {
+            DecodeErrorKind::UnshiftedByte(byte)
+        }
+    }
+
+    /// Error for a byte that cannot be part of the name at this point.
+    fn error_at(&mut self, kind: DecodeErrorKind, byte: u8) -> DecodeError {
+        let position = self.name.len();
+        if self.state == State::Direct {
+            self.sequence_start = position;
+        }
+        self.name.push(if byte.is_ascii() {
+            char::from(byte)
+        } else {
+            char::REPLACEMENT_CHARACTER
+        });
+        self.error(kind, position)
+    }
+
+    /// Error covering the current shift sequence, or the offending byte outside of one.
+    fn error(&self, kind: DecodeErrorKind, position: usize) -> DecodeError {
+        DecodeError::new(
+            kind,
+            &self.name,
+            self.sequence_start..self.name.len(),
+            position,
+        )
+    }
+}
+
+/// Encoder for UTF-7 IMAP mailbox names that are produced in several chunks
+///
+/// Modelled on the `Encoder` of the `encoding_rs` crate, it keeps a shift sequence open between calls,
+/// so the output is the same as encoding the whole name with [`encode_utf7_imap`].
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::Utf7ImapEncoder;
+///
+/// let mut encoder = Utf7ImapEncoder::new();
+/// let mut name = String::new();
+/// encoder.encode_from_utf8_to_string("Drafts/От", &mut name, false);
+/// encoder.encode_from_utf8_to_string("пр", &mut name, true);
+/// assert_eq!(name, "Drafts/&BB4EQgQ,BEA-");
+/// ```
+///
+/// [`encode_utf7_imap`]: crate::encode_utf7_imap
+#[derive(Debug, Clone, Default)]
+pub struct Utf7ImapEncoder {
+    shifted: bool,
+    /// Bits not yet written as a modified BASE64 character.
+    bits: u32,
+    buffer: u32,
+}
+
+impl Utf7ImapEncoder {
+    /// Create an encoder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encode the next chunk of a name, appending the result to `dst`.
+    ///
+    /// Set `last` for the final chunk, so that an open shift sequence is closed.
+    pub fn encode_from_utf8_to_string(&mut self, src: &str, dst: &mut String, last: bool) {
+        let mut units = [0; 2];
+        for c in src.chars() {
+            if is_ascii_custom_char(c) {
+                self.end_shift(dst);
+                dst.push(c);
+                if c == '&' {
+                    dst.push('-');
+                }
+                continue;
+            }
+            if !self.shifted {
+                dst.push('&');
+                self.shifted = true;
+            }
+            for &unit in c.encode_utf16(&mut units).iter() {
+                self.buffer = (self.buffer << 16) | u32::from(unit);
+                self.bits += 16;
+                while self.bits >= 6 {
+                    self.bits -= 6;
+                    dst.push(char::from(
+                        MODIFIED_BASE64[(self.buffer >> self.bits) as usize],
+                    ));
+                    self.buffer &= (1 << self.bits) - 1;
+                }
+            }
+        }
+        if last {
+            self.end_shift(dst);
+        }
+    }
+
+    fn end_shift(&mut self, dst: &mut String) {
+        if !self.shifted {
+            return;
+        }
+        if self.bits > 0 {
+            let value = self.buffer << (6 - self.bits);
+            dst.push(char::from(MODIFIED_BASE64[value as usize]));
+        }
+        dst.push('-');
+        *self = Self::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encode_utf7_imap, try_decode_utf7_imap};
+    use proptest::prelude::*;
+
+    fn decode_in_chunks(text: &[u8], chunk: usize) -> Result<String, DecodeError> {
+        let mut decoder = Utf7ImapDecoder::new();
+        let mut result = String::new();
+        for part in text.chunks(chunk) {
+            decoder.decode_to_string(part, &mut result, false)?;
+        }
+        decoder.decode_to_string(b"", &mut result, true)?;
+        Ok(result)
+    }
+
+    #[test]
+    fn decoder_errors() {
+        let kind = |text: &[u8]| decode_in_chunks(text, 1).map_err(|err| err.kind());
+        assert_eq!(kind(b"&&-"), Err(DecodeErrorKind::InvalidBase64Char('&')));
+        assert_eq!(kind(b"&AOl-"), Err(DecodeErrorKind::BadPadding));
+        assert_eq!(kind(b"&AOkA-"), Err(DecodeErrorKind::OddByteCount));
+        assert_eq!(
+            kind(b"&2D0-"),
+            Err(DecodeErrorKind::UnpairedSurrogate(0xd83d))
+        );
+        assert_eq!(
+            kind(b"&2D0AYQ-"),
+            Err(DecodeErrorKind::UnpairedSurrogate(0xd83d))
+        );
+        assert_eq!(
+            kind(b"&3gA-"),
+            Err(DecodeErrorKind::UnpairedSurrogate(0xde00))
+        );
+        assert_eq!(kind(b"Drafts&BB4"), Err(DecodeErrorKind::UnterminatedShift));
+        assert_eq!(
+            kind(b"caf\xc3\xa9"),
+            Err(DecodeErrorKind::UnshiftedByte(0xc3))
+        );
+        assert_eq!(kind(b"a\x7f"), Err(DecodeErrorKind::UnshiftedByte(0x7f)));
+
+        let err = decode_in_chunks(b"Drafts&BB4E!gQ-", 3).unwrap_err();
+        assert_eq!((err.input(), err.position()), ("Drafts&BB4E!", 11));
+        assert_eq!(err.sequence(), 6..12);
+        let err = decode_in_chunks(b"a&AOnYPQBh-", 3).unwrap_err();
+        assert_eq!((err.input(), err.position()), ("a&AOnYPQBh-", 4));
+        let err = decode_in_chunks(b"x&AOk-Drafts&BB4", 4).unwrap_err();
+        assert_eq!((err.sequence(), err.position()), (12..16, 12));
+        let err = decode_in_chunks(b"caf\xc3\xa9", 2).unwrap_err();
+        assert_eq!(
+            err.snippet().to_string(),
+            "byte 0xc3 is not printable US-ASCII at byte 3\n  caf\u{fffd}\n     ^"
+        );
+    }
+
+    #[test]
+    fn decoder_errors_match_one_shot() {
+        for text in [
+            "&AOl-",
+            "ab&AOkA-",
+            "x&AOk-&2D0-",
+            "&AOnYPQBh-",
+            "&3gA-",
+            "&2D0AYQ",
+            "&2D0AYR-",
+            "&3gA!-",
+        ] {
+            let chunked = decode_in_chunks(text.as_bytes(), 2).unwrap_err();
+            let one_shot = try_decode_utf7_imap(text).unwrap_err();
+            assert_eq!(
+                (chunked.kind(), chunked.position()),
+                (one_shot.kind(), one_shot.position()),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn decoder_writes_nothing_of_failed_sequence() {
+        let mut decoder = Utf7ImapDecoder::new();
+        let mut result = String::new();
+        decoder
+            .decode_to_string(b"x&AOk-&AO", &mut result, false)
+            .unwrap();
+        assert_eq!(result, "xé");
+        let err = decoder
+            .decode_to_string(b"kA-", &mut result, false)
+            .unwrap_err();
+        assert_eq!(result, "xé");
+        assert_eq!(
+            (err.kind(), err.position()),
+            (DecodeErrorKind::OddByteCount, 10)
+        );
+    }
+
+    #[test]
+    fn decoder_resets_after_last_chunk() {
+        let mut decoder = Utf7ImapDecoder::new();
+        let mut result = String::new();
+        assert!(decoder.decode_to_string(b"&AO", &mut result, true).is_err());
+        decoder
+            .decode_to_string(b"&AOk-", &mut result, true)
+            .unwrap();
+        assert_eq!(result, "é");
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(1000))]
+        #[test]
+        fn fuzzy_chunked_matches_one_shot(s in "\\PC*", chunk in 1usize..8) {
+            let encoded = encode_utf7_imap(s.clone());
+            assert_eq!(decode_in_chunks(encoded.as_bytes(), chunk).unwrap(), s);
+
+            let chars: Vec<char> = s.chars().collect();
+            let mut encoder = Utf7ImapEncoder::new();
+            let mut result = String::new();
+            for part in chars.chunks(chunk) {
+                encoder.encode_from_utf8_to_string(&part.iter().collect::<String>(), &mut result, false);
+            }
+            encoder.encode_from_utf8_to_string("", &mut result, true);
+            assert_eq!(result, encoded);
+        }
+
+        #[test]
+        fn fuzzy_chunked_checks_like_one_shot(s in "[&A-Za-z0-9+,-]*", chunk in 1usize..8) {
+            let chunked = decode_in_chunks(s.as_bytes(), chunk);
+            match try_decode_utf7_imap(&s) {
+                Ok(decoded) => assert_eq!(chunked.unwrap(), decoded),
+                // without the rest of the name, an invalid character cannot be told apart
+                // from an unterminated sequence
+                Err(err) if err.kind() == DecodeErrorKind::UnterminatedShift => {
+                    assert!(chunked.is_err())
+                }
+                Err(err) => {
+                    let chunked = chunked.unwrap_err();
+                    assert_eq!(
+                        (chunked.kind(), chunked.position()),
+                        (err.kind(), err.position())
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use alloc::borrow::{Cow, ToOwned};
+use alloc::string::String;
+use core::borrow::Borrow;
+use core::fmt;
+use core::ops::Deref;
+
+use crate::decode::decode_into;
+use crate::{decode, decode_bytes, encode, DecodeError};
+
+/// Borrowed UTF-7 IMAP mailbox name, guaranteed to be well-formed
+///
+/// It holds only printable US-ASCII and decodes without error, so it accepts the same
+/// names as [`decode_bytes`]. This is to [`Utf7ImapString`] what `str` is to `String`.
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::Utf7ImapStr;
+///
+/// let name = Utf7ImapStr::new("&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-").unwrap();
+/// assert_eq!(name.decode(), "Отправленные");
+///
+/// // a decoded name is not a wire name
+/// assert!(Utf7ImapStr::new("Отправленные").is_err());
+/// assert!(Utf7ImapStr::new("Tom & Jerry").is_err());
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(transparent)]
+pub struct Utf7ImapStr(str);
+
+impl Utf7ImapStr {
+    /// Check that `text` is a well-formed encoded name.
+    pub fn new(text: &str) -> Result<&Utf7ImapStr, DecodeError> {
+        validate(text)?;
+        Ok(Utf7ImapStr::new_unchecked(text))
+    }
+
+    fn new_unchecked(text: &str) -> &Utf7ImapStr {
+        // SAFETY: `Utf7ImapStr` is a `repr(transparent)` wrapper around `str`.
+        unsafe { &*(text as *const str as *const Utf7ImapStr) }
+    }
+
+    /// The encoded name.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The encoded name as IMAP wire data.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+
+    /// Decode the name, borrowing it if it contains no shift sequence.
+    pub fn decode(&self) -> Cow<'_, str> {
+        match decode(&self.0) {
+            Ok(text) => text,
+            Err(_) => unreachable!("Utf7ImapStr is validated on construction"),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for &'a Utf7ImapStr {
+    type Error = DecodeError;
+
+    fn try_from(text: &'a str) -> Result<Self, Self::Error> {
+        Utf7ImapStr::new(text)
+    }
+}
+
+impl AsRef<str> for Utf7ImapStr {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for Utf7ImapStr {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl fmt::Display for Utf7ImapStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl ToOwned for Utf7ImapStr {
+    type Owned = Utf7ImapString;
+
+    fn to_owned(&self) -> Utf7ImapString {
+        Utf7ImapString(self.0.into())
+    }
+}
+
+/// Owned UTF-7 IMAP mailbox name, guaranteed to be well-formed
+///
+/// Build it by encoding a name with [`Utf7ImapString::encode`], or by checking an encoded
+/// name with [`TryFrom`]. It dereferences to [`Utf7ImapStr`].
+///
+/// # Usage:
+///
+/// ```
+/// use utf7_imap::Utf7ImapString;
+///
+/// let name = Utf7ImapString::encode("Отправленные");
+/// assert_eq!(name.as_str(), "&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-");
+/// assert_eq!(name.decode(), "Отправленные");
+///
+/// let parsed = Utf7ImapString::try_from("&BB4EQgQ,BEAEMAQyBDsENQQ9BD0ESwQ1-").unwrap();
+/// assert_eq!(parsed, name);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Utf7ImapString(String);
+
+impl Utf7ImapString {
+    /// Encode a mailbox name.
+    pub fn encode(text: &str) -> Self {
+        Utf7ImapString(encode(text).into_owned())
+    }
+
+    /// Borrow the name as a [`Utf7ImapStr`].
+    pub fn as_utf7_imap_str(&self) -> &Utf7ImapStr {
+        Utf7ImapStr::new_unchecked(&self.0)
+    }
+
+    /// Take the encoded name.
+    pub fn into_string(self) -> String {
+        self.0
+    }
+
+    /// The name itself if it is already encoded, otherwise its encoding.
+    #[cfg(feature = "serde")]
+    pub(crate) fn from_either_form(name: String) -> Self {
+        if is_well_formed(name.as_bytes()) {
+            Utf7ImapString(name)
+        } else {
+            Utf7ImapString::encode(&name)
+        }
+    }
+}
+
+impl TryFrom<String> for Utf7ImapString {
+    type Error = DecodeError;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        validate(&text)?;
+        Ok(Utf7ImapString(text))
+    }
+}
+
+impl TryFrom<&str> for Utf7ImapString {
+    type Error = DecodeError;
+
+    fn try_from(text: &str) -> Result<Self, Self::Error> {
+        Utf7ImapStr::new(text).map(ToOwned::to_owned)
+    }
+}
+
+impl From<Utf7ImapString> for String {
+    fn from(name: Utf7ImapString) -> Self {
+        name.0
+    }
+}
+
+impl From<&Utf7ImapStr> for Utf7ImapString {
+    fn from(name: &Utf7ImapStr) -> Self {
+        name.to_owned()
+    }
+}
+
+impl Deref for Utf7ImapString {
+    type Target = Utf7ImapStr;
+
+    fn deref(&self) -> &Utf7ImapStr {
+        self.as_utf7_imap_str()
+    }
+}
+
+impl Borrow<Utf7ImapStr> for Utf7ImapString {
+    fn borrow(&self) -> &Utf7ImapStr {
+        self.as_utf7_imap_str()
+    }
+}
+
+impl AsRef<Utf7ImapStr> for Utf7ImapString {
+    fn as_ref(&self) -> &Utf7ImapStr {
+        self.as_utf7_imap_str()
+    }
+}
+
+impl AsRef<str> for Utf7ImapString {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for Utf7ImapString {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+impl fmt::Display for Utf7ImapString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Drops the decoded text when only checking a name.
+struct Discard;
+
+impl fmt::Write for Discard {
+    fn write_str(&mut self, _: &str) -> fmt::Result {
+        Ok(())
+    }
+}
+
+/// Whether `bytes` is a well-formed encoded name, checked without allocating.
+pub(crate) fn is_well_formed(bytes: &[u8]) -> bool {
+    decode_into(bytes, &mut Discard).is_ok()
+}
+
+/// Check an encoded name without allocating, unless it is malformed.
+fn validate(text: &str) -> Result<(), DecodeError> {
+    if is_well_formed(text.as_bytes()) {
+        Ok(())
+    } else {
+        // Decode again for an error that records the input.
+        decode_bytes(text.as_bytes()).map(drop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DecodeErrorKind;
+    use std::collections::HashSet;
+
+    #[test]
+    fn rejects_malformed_names() {
+        let kind = |text| Utf7ImapStr::new(text).map_err(|err| err.kind());
+        assert_eq!(kind("caf\u{e9}"), Err(DecodeErrorKind::UnshiftedByte(0xc3)));
+        assert_eq!(kind("&AOl-"), Err(DecodeErrorKind::BadPadding));
+        assert_eq!(kind("Drafts&BB4"), Err(DecodeErrorKind::UnterminatedShift));
+        assert!(Utf7ImapStr::new("Tom &- Jerry").is_ok());
+        assert!(Utf7ImapString::try_from(String::from("a\tb")).is_err());
+    }
+
+    #[test]
+    fn borrowed_and_owned_agree() {
+        let owned = Utf7ImapString::encode("th\u{e9}\u{e2}tre");
+        let borrowed: &Utf7ImapStr = "th&AOkA4g-tre".try_into().unwrap();
+        assert_eq!(&*owned, borrowed);
+        assert_eq!(borrowed.to_owned(), owned);
+
+        let mut names = HashSet::new();
+        names.insert(owned);
+        assert!(names.contains(borrowed));
+        assert_eq!(AsRef::<[u8]>::as_ref(borrowed), b"th&AOkA4g-tre".as_slice());
+        assert_eq!(borrowed.to_string(), "th&AOkA4g-tre");
+    }
+}